@@ -1,14 +1,16 @@
-use std::fs;
+use intcode::load::read;
+use std::env;
 
 fn main() {
-    let filename = "../input.txt";
-    let contents = fs::read_to_string(filename).expect("error reading file");
-
-    let original_program: Vec<i32> = contents
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.parse::<i32>().unwrap())
+    let args: Vec<String> = env::args().collect();
+    let filename = args.get(1).map(|s| s.as_str()).unwrap_or("../input.txt");
+    let original_program: Vec<i32> = read(filename)
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {}", filename, e);
+            std::process::exit(1);
+        })
+        .into_iter()
+        .map(|v| v as i32)
         .collect();
 
     let target_value = 19690720;
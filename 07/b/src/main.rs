@@ -1,308 +1,13 @@
+use intcode::session::{take_session_file, Session};
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
 use std::env;
 use std::fs;
 use std::io::Write;
+use std::process;
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq)]
-enum Mode {
-    Position,
-    Immediate,
-}
-
-fn digit_to_mode(d: i32) -> Mode {
-    match d {
-        0 => Mode::Position,
-        1 => Mode::Immediate,
-        _ => {
-            panic!("unexpected mode digit: {}", d);
-        }
-    }
-}
-
-#[derive(Debug, PartialEq)]
-enum Opcode {
-    Addition,
-    Multiplication,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    Break,
-}
-
-fn digit_to_opcode(d: i32) -> Opcode {
-    match d {
-        1 => Opcode::Addition,
-        2 => Opcode::Multiplication,
-        3 => Opcode::Input,
-        4 => Opcode::Output,
-        5 => Opcode::JumpIfTrue,
-        6 => Opcode::JumpIfFalse,
-        7 => Opcode::LessThan,
-        8 => Opcode::Equals,
-        99 => Opcode::Break,
-        _ => {
-            panic!("Illegal opcode {:?}", d);
-        }
-    }
-}
-
-fn parse_op(n: usize) -> (Opcode, Mode, Mode, Mode) {
-    fn x_inner(n: usize, xs: &mut Vec<usize>) {
-        if n >= 10 {
-            x_inner(n / 10, xs);
-        }
-        xs.push(n % 10);
-    }
-    let mut xs = Vec::new();
-    x_inner(n, &mut xs);
-
-    xs.reverse();
-
-    while xs.len() < 5 {
-        xs.push(0);
-    }
-
-    xs.reverse();
-
-    (
-        digit_to_opcode((xs[3] * 10 + xs[4]) as i32),
-        digit_to_mode(xs[0] as i32),
-        digit_to_mode(xs[1] as i32),
-        digit_to_mode(xs[2] as i32),
-    )
-}
-
-#[derive(Debug, PartialEq)]
-enum ReturnState {
-    Error,
-    NeedMoreInput,
-    ProducedOutput,
-    Break,
-}
-
-struct ProgramState {
-    program: Vec<i32>,
-    return_state: ReturnState,
-    inputs: Vec<i32>,
-    outputs: Vec<i32>,
-    pc: usize,
-    input_counter: usize,
-}
-
-fn run_program(s: &mut ProgramState) {
-    while s.pc < s.program.len() {
-        let opcode = s.program[s.pc];
-
-        // println!("s.pc {:?} opcode {:?}", s.pc, opcode);
-
-        let (op, mode3, mode2, mode1) = parse_op(opcode as usize);
-
-        let val1 = s.program[s.pc + 1];
-        let val2 = s.program[s.pc + 2];
-        let val3 = s.program[s.pc + 3];
-
-        // println!(
-        //     "op: {:?}, mode1: {:?}, mode2: {:?}, mode3: {:?}, val1: {:?}, val2: {:?}, val3: {:?}",
-        //     op, mode1, mode2, mode3, val1, val2, val3
-        // );
-
-        match op {
-            Opcode::Addition => {
-                let a;
-                if mode1 == Mode::Position {
-                    a = s.program[val1 as usize];
-                } else {
-                    a = val1;
-                }
-
-                let b;
-                if mode2 == Mode::Position {
-                    b = s.program[val2 as usize];
-                } else {
-                    b = val2;
-                }
-
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                s.program[val3 as usize] = a + b;
-
-                s.pc += 4;
-            }
-            Opcode::Multiplication => {
-                let a;
-                if mode1 == Mode::Position {
-                    a = s.program[val1 as usize];
-                } else {
-                    a = val1;
-                }
-
-                let b;
-                if mode2 == Mode::Position {
-                    b = s.program[val2 as usize];
-                } else {
-                    b = val2;
-                }
-
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                s.program[val3 as usize] = a * b;
-
-                s.pc += 4;
-            }
-            Opcode::Input => {
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                if s.inputs.len() <= s.input_counter {
-                    s.return_state = ReturnState::NeedMoreInput;
-                    return;
-                }
-
-                s.program[val1 as usize] = s.inputs[s.input_counter];
-                s.input_counter += 1;
-
-                s.pc += 2;
-            }
-            Opcode::Output => {
-                if mode1 == Mode::Position {
-                    s.outputs.push(s.program[val1 as usize])
-                } else {
-                    s.outputs.push(val1)
-                }
-
-                s.pc += 2;
-
-                s.return_state = ReturnState::ProducedOutput;
-                return;
-            }
-            Opcode::JumpIfTrue => {
-                let a;
-                if mode1 == Mode::Position {
-                    a = s.program[val1 as usize];
-                } else {
-                    a = val1;
-                }
-
-                let b;
-                if mode2 == Mode::Position {
-                    b = s.program[val2 as usize];
-                } else {
-                    b = val2;
-                }
-
-                if a != 0 {
-                    s.pc = b as usize;
-                } else {
-                    s.pc += 3;
-                }
-            }
-            Opcode::JumpIfFalse => {
-                let a;
-                if mode1 == Mode::Position {
-                    a = s.program[val1 as usize];
-                } else {
-                    a = val1;
-                }
-
-                let b;
-                if mode2 == Mode::Position {
-                    b = s.program[val2 as usize];
-                } else {
-                    b = val2;
-                }
-
-                if a == 0 {
-                    s.pc = b as usize;
-                } else {
-                    s.pc += 3;
-                }
-            }
-            Opcode::LessThan => {
-                let a;
-                if mode1 == Mode::Position {
-                    a = s.program[val1 as usize];
-                } else {
-                    a = val1;
-                }
-
-                let b;
-                if mode2 == Mode::Position {
-                    b = s.program[val2 as usize];
-                } else {
-                    b = val2;
-                }
-
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                if a < b {
-                    s.program[val3 as usize] = 1;
-                } else {
-                    s.program[val3 as usize] = 0;
-                }
-
-                s.pc += 4;
-            }
-            Opcode::Equals => {
-                let a;
-                if mode1 == Mode::Position {
-                    a = s.program[val1 as usize];
-                } else {
-                    a = val1;
-                }
-
-                let b;
-                if mode2 == Mode::Position {
-                    b = s.program[val2 as usize];
-                } else {
-                    b = val2;
-                }
-
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                if a == b {
-                    s.program[val3 as usize] = 1;
-                } else {
-                    s.program[val3 as usize] = 0;
-                }
-
-                s.pc += 4;
-            }
-            Opcode::Break => {
-                s.return_state = ReturnState::Break;
-                return;
-            }
-        }
-    }
-}
-
-fn parse_program(input: &str) -> Vec<i32> {
-    let mut program: Vec<i32> = input
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.parse::<i32>().unwrap())
-        .collect();
-
-    program.push(0);
-    program.push(0);
-    program.push(0);
-
-    program
-}
-
-fn find_combinations(phases_left: &[i32]) -> Vec<Vec<i32>> {
-    let mut res: Vec<Vec<i32>> = Vec::new();
+fn find_combinations(phases_left: &[i64]) -> Vec<Vec<i64>> {
+    let mut res: Vec<Vec<i64>> = Vec::new();
 
     // println!("in: {:?}", phases_left);
 
@@ -336,27 +41,24 @@ fn find_combinations(phases_left: &[i32]) -> Vec<Vec<i32>> {
     return res;
 }
 
+// Also returns the amplifiers of the best configuration, as they were when
+// the feedback loop stopped.
 fn find_optimal_config(
-    phases_input: &[i32],
-    program: &[i32],
-    input_signal: i32,
-) -> (Vec<i32>, i32) {
+    phases_input: &[i64],
+    program: &Arc<Vec<i64>>,
+    input_signal: i64,
+) -> (Vec<i64>, i64, Vec<ProgramState>) {
     let combinations = find_combinations(phases_input);
 
-    let mut res = (vec![], input_signal);
+    let mut res = (vec![], input_signal, vec![]);
 
     for combination in &combinations {
         let mut amp_programs: Vec<ProgramState> = Vec::new();
 
         for i in 0..5 {
-            amp_programs.push(ProgramState {
-                program: program.to_owned(),
-                return_state: ReturnState::Error,
-                inputs: vec![combination[i]],
-                outputs: vec![],
-                pc: 0,
-                input_counter: 0,
-            });
+            let mut amp = ProgramState::from_image(program.clone());
+            amp.inputs.push(combination[i]);
+            amp_programs.push(amp);
         }
 
         let mut last_res = 0;
@@ -383,13 +85,8 @@ fn find_optimal_config(
         println!("combination: {:?}, result: {}", combination, last_res);
         std::io::stdout().flush().unwrap();
 
-        if res.0.len() == 0 {
-            res.0 = combination.clone();
-            res.1 = last_res
-        }
-        if res.1 < last_res {
-            res.0 = combination.clone();
-            res.1 = last_res
+        if res.0.len() == 0 || res.1 < last_res {
+            res = (combination.clone(), last_res, amp_programs);
         }
     }
 
@@ -397,16 +94,32 @@ fn find_optimal_config(
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    // --session <file> records the five amplifiers of the best
+    // configuration, one machine each.
+    let session_file = match take_session_file(&mut args) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("{}\nusage: {} <program> [--session <file>]", e, args[0]);
+            process::exit(1);
+        }
+    };
+
     let filename = &args[1];
 
     let input = fs::read_to_string(filename).expect("error reading file");
 
-    let program = parse_program(&input);
+    let program = Arc::new(parse_program(&input));
 
-    let (order, best_val) = find_optimal_config(&vec![5, 6, 7, 8, 9], &program, 0);
+    let (order, best_val, amps) = find_optimal_config(&vec![5, 6, 7, 8, 9], &program, 0);
 
     println!("result: {:?}, {:?}", order, best_val);
+
+    if let Some(path) = session_file {
+        let sessions: Vec<Session> = amps.iter().map(Session::from_state).collect();
+        Session::save_all(&sessions, path).expect("error writing session file");
+    }
 }
 
 #[cfg(test)]
@@ -417,11 +130,11 @@ mod tests {
     fn test_run() {
         {
             let input = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
-            let program = parse_program(&input);
+            let program = Arc::new(parse_program(&input));
 
             let optimal_res = find_optimal_config(&vec![5, 6, 7, 8, 9], &program, 0);
 
-            println!("{:?}", optimal_res);
+            println!("{:?}", (&optimal_res.0, optimal_res.1));
 
             let expected_result = 139629729;
             assert_eq!(expected_result, optimal_res.1);
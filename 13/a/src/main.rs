@@ -1,328 +1,12 @@
+use intcode::seed::take_seed;
+use intcode::session::Session;
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
 use ncurses::*;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::env;
 use std::fs;
-
-#[derive(Debug, PartialEq, Clone)]
-enum Mode {
-    Position,
-    Immediate,
-    Relative,
-}
-
-fn digit_to_mode(d: i32) -> Mode {
-    match d {
-        0 => Mode::Position,
-        1 => Mode::Immediate,
-        2 => Mode::Relative,
-        _ => {
-            panic!("unexpected mode digit: {}", d);
-        }
-    }
-}
-
-#[derive(Debug, PartialEq, Clone)]
-enum Opcode {
-    Addition,
-    Multiplication,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Break,
-}
-
-fn digit_to_opcode(d: i32) -> Opcode {
-    match d {
-        1 => Opcode::Addition,
-        2 => Opcode::Multiplication,
-        3 => Opcode::Input,
-        4 => Opcode::Output,
-        5 => Opcode::JumpIfTrue,
-        6 => Opcode::JumpIfFalse,
-        7 => Opcode::LessThan,
-        8 => Opcode::Equals,
-        9 => Opcode::AdjustRelativeBase,
-        99 => Opcode::Break,
-        _ => {
-            panic!("Illegal opcode {:?}", d);
-        }
-    }
-}
-
-fn parse_op(n: usize) -> (Opcode, Mode, Mode, Mode) {
-    fn x_inner(n: usize, xs: &mut Vec<usize>) {
-        if n >= 10 {
-            x_inner(n / 10, xs);
-        }
-        xs.push(n % 10);
-    }
-    let mut xs = Vec::new();
-    x_inner(n, &mut xs);
-
-    xs.reverse();
-
-    while xs.len() < 5 {
-        xs.push(0);
-    }
-
-    xs.reverse();
-
-    (
-        digit_to_opcode((xs[3] * 10 + xs[4]) as i32),
-        digit_to_mode(xs[0] as i32),
-        digit_to_mode(xs[1] as i32),
-        digit_to_mode(xs[2] as i32),
-    )
-}
-
-#[derive(Debug, PartialEq, Clone)]
-enum ReturnState {
-    NeedMoreInput,
-    ProducedOutput,
-    Break,
-}
-
-#[derive(Debug, PartialEq, Clone)]
-struct ProgramState {
-    program: Vec<i64>,
-    return_state: ReturnState,
-    inputs: Vec<i64>,
-    outputs: Vec<i64>,
-    pc: usize,
-    input_counter: usize,
-    relative_base: i64,
-}
-
-fn run_program(s: &mut ProgramState) {
-    let mut counter = 0;
-
-    while s.pc < s.program.len() && counter < 1000000 {
-        counter += 1;
-
-        let opcode = s.program[s.pc];
-
-        // println!("s.pc {:?} opcode {:?}", s.pc, opcode);
-
-        let (op, mode3, mode2, mode1) = parse_op(opcode as usize);
-
-        let val1 = s.program[s.pc + 1];
-        let val2 = s.program[s.pc + 2];
-        let val3 = s.program[s.pc + 3];
-
-        // println!(
-        //     "op: {:?}, mode1: {:?}, mode2: {:?}, mode3: {:?}, val1: {:?}, val2: {:?}, val3: {:?}, a: {}, b: {}",
-        //     op, mode1, mode2, mode3, val1, val2, val3, a, b
-        // );
-
-        match op {
-            Opcode::Addition => {
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                match mode3 {
-                    Mode::Position => s.program[val3 as usize] = a + b,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => s.program[(s.relative_base + val3) as usize] = a + b,
-                };
-
-                s.pc += 4;
-            }
-            Opcode::Multiplication => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                match mode3 {
-                    Mode::Position => s.program[val3 as usize] = a * b,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => s.program[(s.relative_base + val3) as usize] = a * b,
-                };
-
-                s.pc += 4;
-            }
-            Opcode::Input => {
-                if s.inputs.len() <= s.input_counter {
-                    s.return_state = ReturnState::NeedMoreInput;
-                    return;
-                }
-
-                match mode1 {
-                    Mode::Position => s.program[val1 as usize] = s.inputs[s.input_counter],
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => {
-                        s.program[(s.relative_base + val1) as usize] = s.inputs[s.input_counter]
-                    }
-                };
-
-                s.input_counter += 1;
-
-                s.pc += 2;
-            }
-            Opcode::Output => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-                s.outputs.push(a);
-
-                s.pc += 2;
-
-                s.return_state = ReturnState::ProducedOutput;
-                return;
-            }
-            Opcode::JumpIfTrue => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                if a != 0 {
-                    s.pc = b as usize;
-                } else {
-                    s.pc += 3;
-                }
-            }
-            Opcode::JumpIfFalse => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                if a == 0 {
-                    s.pc = b as usize;
-                } else {
-                    s.pc += 3;
-                }
-            }
-            Opcode::LessThan => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                let c_index = match mode3 {
-                    Mode::Position => val3 as usize,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => (s.relative_base + val3) as usize,
-                };
-
-                if a < b {
-                    s.program[c_index] = 1;
-                } else {
-                    s.program[c_index] = 0;
-                }
-
-                s.pc += 4;
-            }
-            Opcode::Equals => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                let c_index = match mode3 {
-                    Mode::Position => val3 as usize,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => (s.relative_base + val3) as usize,
-                };
-
-                if a == b {
-                    s.program[c_index] = 1;
-                } else {
-                    s.program[c_index] = 0;
-                }
-
-                s.pc += 4;
-            }
-            Opcode::AdjustRelativeBase => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                s.relative_base += a;
-
-                s.pc += 2;
-            }
-            Opcode::Break => {
-                s.return_state = ReturnState::Break;
-                return;
-            }
-        }
-    }
-
-    if counter >= 1000000 {
-        s.return_state = ReturnState::Break;
-    }
-}
-
-fn parse_program(input: &str) -> Vec<i64> {
-    let mut program: Vec<i64> = input
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
-
-    program.resize(1000000, 0);
-
-    program
-}
+use std::process;
 
 #[derive(Debug, PartialEq, Clone)]
 struct Vec2 {
@@ -454,28 +138,67 @@ fn process_output(state: &mut State) -> bool {
     return bounce_detected;
 }
 
+// Joystick input for the next stretch of play: mostly random, sometimes
+// chasing the ball.
+fn push_moves<R: Rng>(state: &mut State, rng: &mut R) {
+    let r = rng.gen_range(0, 20);
+    if r < 10 {
+        state.program.inputs.push(rng.gen_range(-1, 2));
+    } else if r < 13 {
+        state.program.inputs.push(-1);
+        state.program.inputs.push(-1);
+        state.program.inputs.push(-1);
+        state.program.inputs.push(-1);
+    } else if r < 16 {
+        state.program.inputs.push(1);
+        state.program.inputs.push(1);
+        state.program.inputs.push(1);
+        state.program.inputs.push(1);
+    } else {
+        if state.ball_x < state.paddle_x {
+            state.program.inputs.push(-1);
+            state.program.inputs.push(-1);
+            state.program.inputs.push(-1);
+            state.program.inputs.push(-1);
+            state.program.inputs.push(-1);
+        } else {
+            state.program.inputs.push(1);
+            state.program.inputs.push(1);
+            state.program.inputs.push(1);
+            state.program.inputs.push(1);
+            state.program.inputs.push(1);
+        }
+    }
+}
+
 fn main() {
     let mut counter = 0;
 
-    let mut rng = rand::thread_rng();
+    let mut args: Vec<String> = env::args().collect();
+
+    // --seed <n> makes the moves and the backtracking reproducible.
+    let mut rng = match take_seed(&mut args) {
+        Ok(Some(seed)) => StdRng::seed_from_u64(seed),
+        Ok(None) => StdRng::from_entropy(),
+        Err(e) => {
+            eprintln!(
+                "{}\nusage: {} <program> [session file] [--seed <n>]",
+                e, args[0]
+            );
+            process::exit(1);
+        }
+    };
 
-    let args: Vec<String> = env::args().collect();
     let filename = &args[1];
+    let session_file = args.get(2);
+    let mut best_score = -1;
 
     let input = fs::read_to_string(filename).expect("error reading file");
 
     let mut state = State {
         map: Vec::new(),
         score: 0,
-        program: ProgramState {
-            program: parse_program(&input),
-            return_state: ReturnState::ProducedOutput,
-            inputs: vec![],
-            outputs: vec![],
-            pc: 0,
-            input_counter: 0,
-            relative_base: 0,
-        },
+        program: ProgramState::new(parse_program(&input)),
         ball_x: 0,
         paddle_x: 0,
         ball_y: 0,
@@ -509,34 +232,7 @@ fn main() {
             run_program(&mut state.program);
 
             if state.program.return_state == ReturnState::NeedMoreInput {
-                let r = rng.gen_range(0, 20);
-                if r < 10 {
-                    state.program.inputs.push(rng.gen_range(-1, 2));
-                } else if r < 13 {
-                    state.program.inputs.push(-1);
-                    state.program.inputs.push(-1);
-                    state.program.inputs.push(-1);
-                    state.program.inputs.push(-1);
-                } else if r < 16 {
-                    state.program.inputs.push(1);
-                    state.program.inputs.push(1);
-                    state.program.inputs.push(1);
-                    state.program.inputs.push(1);
-                } else {
-                    if state.ball_x < state.paddle_x {
-                        state.program.inputs.push(-1);
-                        state.program.inputs.push(-1);
-                        state.program.inputs.push(-1);
-                        state.program.inputs.push(-1);
-                        state.program.inputs.push(-1);
-                    } else {
-                        state.program.inputs.push(1);
-                        state.program.inputs.push(1);
-                        state.program.inputs.push(1);
-                        state.program.inputs.push(1);
-                        state.program.inputs.push(1);
-                    }
-                }
+                push_moves(&mut state, &mut rng);
             }
 
             if state.program.return_state == ReturnState::ProducedOutput
@@ -557,6 +253,15 @@ fn main() {
         }
 
         if state.program.return_state == ReturnState::Break {
+            if let Some(path) = session_file {
+                if state.score > best_score {
+                    best_score = state.score;
+                    Session::from_state(&state.program)
+                        .save(path)
+                        .expect("error writing session file");
+                }
+            }
+
             state = old_states.last().unwrap().clone();
 
             if state.score < 18000 {
@@ -583,3 +288,33 @@ fn main() {
 
     endwin();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(seed: u64) -> Vec<i64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut state = State {
+            map: Vec::new(),
+            score: 0,
+            program: ProgramState::new(vec![99]),
+            ball_x: 10,
+            paddle_x: 12,
+            ball_y: 0,
+            old_ball_y: 0,
+        };
+        for _ in 0..100 {
+            push_moves(&mut state, &mut rng);
+        }
+        state.program.inputs
+    }
+
+    #[test]
+    fn test_run() {
+        let a = moves(2019);
+        assert_eq!(a, moves(2019));
+        assert!(a != moves(7));
+        assert!(a.iter().all(|m| (-1..=1).contains(m)));
+    }
+}
@@ -1,328 +1,12 @@
+use intcode::seed::take_seed;
+use intcode::session::{take_session_file, Session};
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
 use ncurses::*;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::env;
 use std::fs;
-
-#[derive(Debug, PartialEq, Clone)]
-enum Mode {
-    Position,
-    Immediate,
-    Relative,
-}
-
-fn digit_to_mode(d: i32) -> Mode {
-    match d {
-        0 => Mode::Position,
-        1 => Mode::Immediate,
-        2 => Mode::Relative,
-        _ => {
-            panic!("unexpected mode digit: {}", d);
-        }
-    }
-}
-
-#[derive(Debug, PartialEq, Clone)]
-enum Opcode {
-    Addition,
-    Multiplication,
-    Input,
-    Output,
-    JumpIfTrue,
-    JumpIfFalse,
-    LessThan,
-    Equals,
-    AdjustRelativeBase,
-    Break,
-}
-
-fn digit_to_opcode(d: i32) -> Opcode {
-    match d {
-        1 => Opcode::Addition,
-        2 => Opcode::Multiplication,
-        3 => Opcode::Input,
-        4 => Opcode::Output,
-        5 => Opcode::JumpIfTrue,
-        6 => Opcode::JumpIfFalse,
-        7 => Opcode::LessThan,
-        8 => Opcode::Equals,
-        9 => Opcode::AdjustRelativeBase,
-        99 => Opcode::Break,
-        _ => {
-            panic!("Illegal opcode {:?}", d);
-        }
-    }
-}
-
-fn parse_op(n: usize) -> (Opcode, Mode, Mode, Mode) {
-    fn x_inner(n: usize, xs: &mut Vec<usize>) {
-        if n >= 10 {
-            x_inner(n / 10, xs);
-        }
-        xs.push(n % 10);
-    }
-    let mut xs = Vec::new();
-    x_inner(n, &mut xs);
-
-    xs.reverse();
-
-    while xs.len() < 5 {
-        xs.push(0);
-    }
-
-    xs.reverse();
-
-    (
-        digit_to_opcode((xs[3] * 10 + xs[4]) as i32),
-        digit_to_mode(xs[0] as i32),
-        digit_to_mode(xs[1] as i32),
-        digit_to_mode(xs[2] as i32),
-    )
-}
-
-#[derive(Debug, PartialEq, Clone)]
-enum ReturnState {
-    NeedMoreInput,
-    ProducedOutput,
-    Break,
-}
-
-#[derive(Debug, PartialEq, Clone)]
-struct ProgramState {
-    program: Vec<i64>,
-    return_state: ReturnState,
-    inputs: Vec<i64>,
-    outputs: Vec<i64>,
-    pc: usize,
-    input_counter: usize,
-    relative_base: i64,
-}
-
-fn run_program(s: &mut ProgramState) {
-    let mut counter = 0;
-
-    while s.pc < s.program.len() && counter < 1000000 {
-        counter += 1;
-
-        let opcode = s.program[s.pc];
-
-        // println!("s.pc {:?} opcode {:?}", s.pc, opcode);
-
-        let (op, mode3, mode2, mode1) = parse_op(opcode as usize);
-
-        let val1 = s.program[s.pc + 1];
-        let val2 = s.program[s.pc + 2];
-        let val3 = s.program[s.pc + 3];
-
-        // println!(
-        //     "op: {:?}, mode1: {:?}, mode2: {:?}, mode3: {:?}, val1: {:?}, val2: {:?}, val3: {:?}, a: {}, b: {}",
-        //     op, mode1, mode2, mode3, val1, val2, val3, a, b
-        // );
-
-        match op {
-            Opcode::Addition => {
-                if mode3 == Mode::Immediate {
-                    panic!("illegal immediate mode");
-                }
-
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                match mode3 {
-                    Mode::Position => s.program[val3 as usize] = a + b,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => s.program[(s.relative_base + val3) as usize] = a + b,
-                };
-
-                s.pc += 4;
-            }
-            Opcode::Multiplication => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                match mode3 {
-                    Mode::Position => s.program[val3 as usize] = a * b,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => s.program[(s.relative_base + val3) as usize] = a * b,
-                };
-
-                s.pc += 4;
-            }
-            Opcode::Input => {
-                if s.inputs.len() <= s.input_counter {
-                    s.return_state = ReturnState::NeedMoreInput;
-                    return;
-                }
-
-                match mode1 {
-                    Mode::Position => s.program[val1 as usize] = s.inputs[s.input_counter],
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => {
-                        s.program[(s.relative_base + val1) as usize] = s.inputs[s.input_counter]
-                    }
-                };
-
-                s.input_counter += 1;
-
-                s.pc += 2;
-            }
-            Opcode::Output => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-                s.outputs.push(a);
-
-                s.pc += 2;
-
-                s.return_state = ReturnState::ProducedOutput;
-                return;
-            }
-            Opcode::JumpIfTrue => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                if a != 0 {
-                    s.pc = b as usize;
-                } else {
-                    s.pc += 3;
-                }
-            }
-            Opcode::JumpIfFalse => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                if a == 0 {
-                    s.pc = b as usize;
-                } else {
-                    s.pc += 3;
-                }
-            }
-            Opcode::LessThan => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                let c_index = match mode3 {
-                    Mode::Position => val3 as usize,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => (s.relative_base + val3) as usize,
-                };
-
-                if a < b {
-                    s.program[c_index] = 1;
-                } else {
-                    s.program[c_index] = 0;
-                }
-
-                s.pc += 4;
-            }
-            Opcode::Equals => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                let b = match mode2 {
-                    Mode::Position => s.program[val2 as usize],
-                    Mode::Immediate => val2,
-                    Mode::Relative => s.program[(s.relative_base + val2) as usize],
-                };
-
-                let c_index = match mode3 {
-                    Mode::Position => val3 as usize,
-                    Mode::Immediate => panic!("illegal immediate mode"),
-                    Mode::Relative => (s.relative_base + val3) as usize,
-                };
-
-                if a == b {
-                    s.program[c_index] = 1;
-                } else {
-                    s.program[c_index] = 0;
-                }
-
-                s.pc += 4;
-            }
-            Opcode::AdjustRelativeBase => {
-                let a = match mode1 {
-                    Mode::Position => s.program[val1 as usize],
-                    Mode::Immediate => val1,
-                    Mode::Relative => s.program[(s.relative_base + val1) as usize],
-                };
-
-                s.relative_base += a;
-
-                s.pc += 2;
-            }
-            Opcode::Break => {
-                s.return_state = ReturnState::Break;
-                return;
-            }
-        }
-    }
-
-    if counter >= 1000000 {
-        s.return_state = ReturnState::Break;
-    }
-}
-
-fn parse_program(input: &str) -> Vec<i64> {
-    let mut program: Vec<i64> = input
-        .split(',')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .map(|s| s.parse::<i64>().unwrap())
-        .collect();
-
-    program.resize(1000000, 0);
-
-    program
-}
+use std::process;
 
 #[derive(Debug, PartialEq, Clone)]
 struct Vec2 {
@@ -428,27 +112,93 @@ fn set_material(pos: Vec2, map: &mut Vec<Location>, material: Material) {
     });
 }
 
-fn main() {
-    let mut count: i64 = 0;
+// Moves the droid one step in a random direction and records what it found.
+fn explore_step<R: Rng>(state: &mut State, rng: &mut R) {
+    while state.program.return_state != ReturnState::NeedMoreInput {
+        run_program(&mut state.program);
+    }
 
-    let mut rng = rand::thread_rng();
+    let dir = match rng.gen_range(0, 4) {
+        0 => Direction::Up,
+        1 => Direction::Down,
+        2 => Direction::Left,
+        3 => Direction::Right,
+        _ => panic!("unexpected"),
+    };
 
-    let args: Vec<String> = env::args().collect();
-    let filename = &args[1];
+    state.program.inputs.push(match dir {
+        Direction::Up => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+        Direction::Right => 4,
+    });
 
-    let input = fs::read_to_string(filename).expect("error reading file");
+    run_program(&mut state.program);
+
+    while state.program.return_state != ReturnState::ProducedOutput {
+        run_program(&mut state.program);
+    }
+
+    let mut dx = 0;
+    let mut dy = 0;
+    match dir {
+        Direction::Up => {
+            dy = 1;
+        }
+        Direction::Down => {
+            dy = -1;
+        }
+        Direction::Left => {
+            dx = 1;
+        }
+        Direction::Right => {
+            dx = -1;
+        }
+    }
 
+    match state.program.outputs.last().unwrap() {
+        0 => {
+            set_material(
+                Vec2 {
+                    x: state.robot_pos.x + dx,
+                    y: state.robot_pos.y + dy,
+                },
+                &mut state.map,
+                Material::Wall,
+            );
+        }
+        1 => {
+            state.robot_pos.x += dx;
+            state.robot_pos.y += dy;
+            set_material(
+                Vec2 {
+                    x: state.robot_pos.x,
+                    y: state.robot_pos.y,
+                },
+                &mut state.map,
+                Material::Empty,
+            );
+        }
+        2 => {
+            state.robot_pos.x += dx;
+            state.robot_pos.y += dy;
+            set_material(
+                Vec2 {
+                    x: state.robot_pos.x,
+                    y: state.robot_pos.y,
+                },
+                &mut state.map,
+                Material::OxygenTank,
+            );
+        }
+        _ => panic!("unexpected"),
+    }
+}
+
+fn new_state(input: &str) -> State {
     let mut state = State {
         map: Vec::new(),
-        program: ProgramState {
-            program: parse_program(&input),
-            return_state: ReturnState::ProducedOutput,
-            inputs: vec![],
-            outputs: vec![],
-            pc: 0,
-            input_counter: 0,
-            relative_base: 0,
-        },
+        program: ProgramState::new(parse_program(input)),
         robot_pos: Vec2 { x: 0, y: 0 },
     };
 
@@ -458,94 +208,61 @@ fn main() {
         dist: 0,
     });
 
-    initscr();
-    noecho();
+    state
+}
 
-    run_program(&mut state.program);
+fn main() {
+    let mut count: i64 = 0;
 
-    while state.program.return_state != ReturnState::Break {
-        while state.program.return_state != ReturnState::NeedMoreInput {
-            run_program(&mut state.program);
+    let mut args: Vec<String> = env::args().collect();
+
+    // --seed <n> makes the random walk reproducible.
+    let mut rng = match take_seed(&mut args) {
+        Ok(Some(seed)) => StdRng::seed_from_u64(seed),
+        Ok(None) => StdRng::from_entropy(),
+        Err(e) => {
+            eprintln!(
+                "{}\nusage: {} <program> [--seed <n>] [--session <file>]",
+                e, args[0]
+            );
+            process::exit(1);
         }
+    };
 
-        let dir = match rng.gen_range(0, 4) {
-            0 => Direction::Up,
-            1 => Direction::Down,
-            2 => Direction::Left,
-            3 => Direction::Right,
-            _ => panic!("unexpected"),
-        };
-
-        state.program.inputs.push(match dir {
-            Direction::Up => 1,
-            Direction::Down => 2,
-            Direction::Left => 3,
-            Direction::Right => 4,
-        });
+    // --session <file> records the walk, saved each time the map is drawn.
+    let session_file = match take_session_file(&mut args) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!(
+                "{}\nusage: {} <program> [--seed <n>] [--session <file>]",
+                e, args[0]
+            );
+            process::exit(1);
+        }
+    };
 
-        run_program(&mut state.program);
+    let filename = &args[1];
 
-        while state.program.return_state != ReturnState::ProducedOutput {
-            run_program(&mut state.program);
-        }
+    let input = fs::read_to_string(filename).expect("error reading file");
 
-        let mut dx = 0;
-        let mut dy = 0;
-        match dir {
-            Direction::Up => {
-                dy = 1;
-            }
-            Direction::Down => {
-                dy = -1;
-            }
-            Direction::Left => {
-                dx = 1;
-            }
-            Direction::Right => {
-                dx = -1;
-            }
-        }
+    let mut state = new_state(&input);
 
-        match state.program.outputs.last().unwrap() {
-            0 => {
-                set_material(
-                    Vec2 {
-                        x: state.robot_pos.x + dx,
-                        y: state.robot_pos.y + dy,
-                    },
-                    &mut state.map,
-                    Material::Wall,
-                );
-            }
-            1 => {
-                state.robot_pos.x += dx;
-                state.robot_pos.y += dy;
-                set_material(
-                    Vec2 {
-                        x: state.robot_pos.x,
-                        y: state.robot_pos.y,
-                    },
-                    &mut state.map,
-                    Material::Empty,
-                );
-            }
-            2 => {
-                state.robot_pos.x += dx;
-                state.robot_pos.y += dy;
-                set_material(
-                    Vec2 {
-                        x: state.robot_pos.x,
-                        y: state.robot_pos.y,
-                    },
-                    &mut state.map,
-                    Material::OxygenTank,
-                );
-            }
-            _ => panic!("unexpected"),
-        }
+    initscr();
+    noecho();
+
+    run_program(&mut state.program);
+
+    while state.program.return_state != ReturnState::Break {
+        explore_step(&mut state, &mut rng);
 
         count += 1;
         if count % 100000 == 0 {
+            if let Some(path) = &session_file {
+                Session::from_state(&state.program)
+                    .save(path)
+                    .expect("error writing session file");
+            }
+
             for loc in &mut state.map {
                 loc.dist = -1;
                 if loc.material == Material::OxygenTank {
@@ -609,3 +326,31 @@ fn main() {
 
     endwin();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A corridor three cells long, walls all around, oxygen at the far end.
+    const MAZE: &str = "3,100,1008,100,3,101,1008,100,4,102,1002,102,-1,102,1,101,102,103,1006,103,55,1,104,103,105,1007,105,0,106,1005,106,55,1007,105,3,106,1006,106,55,1001,105,0,104,1008,104,2,106,1005,106,60,104,1,1105,1,0,104,0,1105,1,0,104,2,1105,1,0";
+
+    fn explore(seed: u64, steps: usize) -> State {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut state = new_state(MAZE);
+        for _ in 0..steps {
+            explore_step(&mut state, &mut rng);
+        }
+        state
+    }
+
+    #[test]
+    fn test_run() {
+        // The same seed takes the same path, another seed a different one.
+        let a = explore(2019, 200);
+        assert_eq!(a, explore(2019, 200));
+        assert!(a.program.inputs != explore(7, 200).program.inputs);
+
+        let tank = a.map.iter().find(|l| l.material == Material::OxygenTank);
+        assert_eq!(tank.map(|l| (l.pos.x, l.pos.y)), Some((2, 0)));
+    }
+}
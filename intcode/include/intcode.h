@@ -0,0 +1,55 @@
+// Generated from src/ffi.rs by tests/ffi.rs. To update it, run
+//   INTCODE_UPDATE_HEADER=1 cargo test --test ffi
+
+#ifndef INTCODE_H
+#define INTCODE_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct IntcodeMachine IntcodeMachine;
+
+#define INTCODE_ERROR -1
+#define INTCODE_HALTED 0
+#define INTCODE_NEED_INPUT 1
+#define INTCODE_BUDGET 2
+#define INTCODE_LOOP 3
+
+// Comma separated program text; NULL if it doesn't parse.
+IntcodeMachine *intcode_create(const char *text);
+
+void intcode_destroy(IntcodeMachine *m);
+
+// An independent copy of the machine as it is now, outputs included. m
+// must not be NULL.
+IntcodeMachine *intcode_snapshot(const IntcodeMachine *m);
+
+void intcode_push_input(IntcodeMachine *m, int64_t value);
+
+// Runs until the machine halts, needs input it doesn't have, or has run
+// budget instructions. Outputs queue up for intcode_pop_output.
+int intcode_run(IntcodeMachine *m, uint64_t budget);
+
+// Makes intcode_run return INTCODE_LOOP when the machine is caught in a
+// loop it can never leave, instead of using up its budget.
+void intcode_detect_loops(IntcodeMachine *m);
+
+// Takes the oldest output not yet popped. Returns 1 if there was one, 0 if
+// not. Popped outputs are dropped once the caller has taken them all.
+int intcode_pop_output(IntcodeMachine *m, int64_t *value);
+
+// Memory access returns 0, or INTCODE_ERROR for an address out of range.
+int intcode_read(const IntcodeMachine *m, uint64_t addr, int64_t *value);
+
+int intcode_write(IntcodeMachine *m, uint64_t addr, int64_t value);
+
+uint64_t intcode_steps(const IntcodeMachine *m);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
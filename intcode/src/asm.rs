@@ -0,0 +1,262 @@
+// Assembler for the syntax the disassembler prints, plus labels:
+//
+//   # comments start with # or ;
+//   start:  in [x]
+//           mul [x], 2, [rb+1]
+//           jt [x], start
+//   x:      data 0, -1, start+2
+//
+// Operands are [addr] for position mode, a plain value for immediate mode
+// and [rb+n] or [rb-n] for relative mode. Anywhere a number goes, a label
+// or label+n works too.
+
+use crate::registry::{Param, Registry};
+use crate::Mode;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Label(String, i64),
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Ins(i64, Vec<(Mode, Expr)>),
+    Data(Vec<Expr>),
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_expr(s: &str) -> Result<Expr, String> {
+    let s = s.trim();
+    if let Ok(n) = s.parse::<i64>() {
+        return Ok(Expr::Number(n));
+    }
+    let (name, offset) = match s.find(['+', '-']) {
+        Some(i) => {
+            let offset = s[i..]
+                .replace(' ', "")
+                .trim_start_matches('+')
+                .parse::<i64>()
+                .map_err(|_| format!("bad offset in {}", s))?;
+            (s[..i].trim(), offset)
+        }
+        None => (s, 0),
+    };
+    if !is_label(name) {
+        return Err(format!("bad value {}", s));
+    }
+    Ok(Expr::Label(name.to_owned(), offset))
+}
+
+fn parse_operand(s: &str) -> Result<(Mode, Expr), String> {
+    let s = s.trim();
+    if !s.starts_with('[') {
+        return Ok((Mode::Immediate, parse_expr(s)?));
+    }
+    let inner = s
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| format!("unclosed [ in {}", s))?
+        .trim();
+    if inner == "rb" {
+        return Ok((Mode::Relative, Expr::Number(0)));
+    }
+    if let Some(rest) = inner.strip_prefix("rb") {
+        let rest = rest.trim_start();
+        if let Some(e) = rest.strip_prefix('+') {
+            return Ok((Mode::Relative, parse_expr(e)?));
+        }
+        if rest.starts_with('-') {
+            return Ok((Mode::Relative, parse_expr(rest)?));
+        }
+    }
+    Ok((Mode::Position, parse_expr(inner)?))
+}
+
+pub fn assemble(text: &str) -> Result<Vec<i64>, AsmError> {
+    assemble_with(&Registry::builtin(), text)
+}
+
+pub fn assemble_with(registry: &Registry, text: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels: HashMap<String, i64> = HashMap::new();
+    let mut stmts: Vec<(usize, Stmt)> = Vec::new();
+    let mut addr = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |message: String| AsmError {
+            line: line_no,
+            message,
+        };
+
+        let mut line = line.split(['#', ';']).next().unwrap().trim();
+        while let Some(colon) = line.find(':') {
+            let name = line[..colon].trim();
+            if !is_label(name) {
+                break;
+            }
+            if labels.insert(name.to_owned(), addr).is_some() {
+                return Err(err(format!("label {} defined twice", name)));
+            }
+            line = line[colon + 1..].trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let operands: Vec<&str> = if rest.trim().is_empty() {
+            vec![]
+        } else {
+            rest.split(',').collect()
+        };
+
+        let stmt = if word == "data" {
+            let values = operands
+                .iter()
+                .map(|o| parse_expr(o))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(err)?;
+            addr += values.len() as i64;
+            Stmt::Data(values)
+        } else {
+            let def = registry
+                .by_mnemonic(word)
+                .ok_or_else(|| err(format!("unknown instruction {}", word)))?;
+            if operands.len() != def.params.len() {
+                return Err(err(format!(
+                    "{} takes {} operands, got {}",
+                    word,
+                    def.params.len(),
+                    operands.len()
+                )));
+            }
+            let operands = operands
+                .iter()
+                .map(|o| parse_operand(o))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(err)?;
+            for (param, (mode, _)) in def.params.iter().zip(&operands) {
+                if *param == Param::Write && *mode == Mode::Immediate {
+                    return Err(err(format!("{} cannot write to an immediate", word)));
+                }
+            }
+            addr += 1 + operands.len() as i64;
+            Stmt::Ins(def.code, operands)
+        };
+        stmts.push((line_no, stmt));
+    }
+
+    let mut res = Vec::new();
+    for (line, stmt) in stmts {
+        let value = |e: &Expr| match e {
+            Expr::Number(n) => Ok(*n),
+            Expr::Label(name, offset) => match labels.get(name) {
+                Some(addr) => Ok(addr + offset),
+                None => Err(AsmError {
+                    line,
+                    message: format!("unknown label {}", name),
+                }),
+            },
+        };
+        match stmt {
+            Stmt::Data(values) => {
+                for v in &values {
+                    res.push(value(v)?);
+                }
+            }
+            Stmt::Ins(code, operands) => {
+                let mut op = code;
+                let mut scale = 100;
+                for (mode, _) in &operands {
+                    op += scale
+                        * match mode {
+                            Mode::Position => 0,
+                            Mode::Immediate => 1,
+                            Mode::Relative => 2,
+                        };
+                    scale *= 10;
+                }
+                res.push(op);
+                for (_, e) in &operands {
+                    res.push(value(e)?);
+                }
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{disassemble, trimmed_len};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_run() {
+        let program = assemble(
+            "
+            # Counts down from the input, printing each value.
+                    in [n]
+            loop:   out [n]
+                    add [n], -1, [n]
+                    jt [n], loop
+                    hlt
+            n:      data 0
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            [3, 12, 4, 12, 1001, 12, -1, 12, 1005, 12, 2, 99, 0]
+        );
+
+        assert_eq!(
+            assemble("arb 5\nout [rb-1]\nadd [rb], 1, [rb+2]").unwrap(),
+            [109, 5, 204, -1, 21201, 0, 1, 2]
+        );
+
+        // Disassembled listings assemble back to the same program.
+        let boost = crate::parse_program(include_str!("../../09/input"));
+        let boost = &boost[..trimmed_len(&boost)];
+        let listing: Vec<String> = disassemble(boost, &BTreeSet::new())
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(assemble(&listing.join("\n")).unwrap(), boost);
+
+        assert_eq!(
+            assemble("jt 1, nowhere"),
+            Err(AsmError {
+                line: 1,
+                message: "unknown label nowhere".to_owned()
+            })
+        );
+        assert_eq!(assemble("\n\nin 5").unwrap_err().line, 3);
+        assert_eq!(assemble("add 1, 2").unwrap_err().line, 1);
+    }
+}
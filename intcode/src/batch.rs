@@ -0,0 +1,137 @@
+// Runs many independent machines from one program image on a pool of
+// threads. Every job starts from the same pristine image, shared between
+// all of them; a job only copies the pages it writes to.
+
+use crate::{step_program, ProgramState, ReturnState};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum JobEnd {
+    Halted,
+    NeedMoreInput,
+    StepLimit,
+    InfiniteLoop { start: usize, end: usize },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct JobResult {
+    pub outputs: Vec<i64>,
+    pub end: JobEnd,
+    pub steps: u64,
+}
+
+pub struct Batch {
+    image: Arc<Vec<i64>>,
+    pub threads: usize,
+    pub max_steps: u64,
+    pub detect_loops: bool,
+}
+
+impl Batch {
+    pub fn new(program: Vec<i64>) -> Batch {
+        Batch {
+            image: Arc::new(program),
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            max_steps: 100000000,
+            detect_loops: false,
+        }
+    }
+
+    // Runs one job per input vector until it halts, starves for input or
+    // runs out of steps. Results come back in the order of the inputs.
+    pub fn run<I: IntoIterator<Item = Vec<i64>>>(&self, jobs: I) -> Vec<JobResult> {
+        let jobs: Vec<Vec<i64>> = jobs.into_iter().collect();
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; jobs.len()]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1).min(jobs.len()) {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= jobs.len() {
+                        return;
+                    }
+                    let res = self.run_job(jobs[i].clone());
+                    results.lock().unwrap()[i] = Some(res);
+                });
+            }
+        });
+
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    fn run_job(&self, inputs: Vec<i64>) -> JobResult {
+        let mut s = ProgramState::from_image(self.image.clone());
+        s.inputs = inputs;
+        if self.detect_loops {
+            s.detect_loops();
+        }
+
+        let end = loop {
+            if s.steps >= self.max_steps || s.pc >= s.program.len() {
+                break JobEnd::StepLimit;
+            }
+            if step_program(&mut s) {
+                match s.return_state {
+                    ReturnState::ProducedOutput => {}
+                    ReturnState::NeedMoreInput => break JobEnd::NeedMoreInput,
+                    ReturnState::Break => break JobEnd::Halted,
+                    ReturnState::InfiniteLoop { start, end } => {
+                        break JobEnd::InfiniteLoop { start, end }
+                    }
+                }
+            }
+        };
+
+        JobResult {
+            outputs: s.outputs,
+            end,
+            steps: s.steps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, run_program};
+
+    #[test]
+    fn test_run() {
+        // Day 19: probe a corner of the tractor beam both ways.
+        let program = parse_program(include_str!("../../19/input.txt"));
+        let coords: Vec<Vec<i64>> = (0..10)
+            .flat_map(|y| (0..10).map(move |x| vec![x, y]))
+            .collect();
+
+        let results = Batch::new(program.clone()).run(coords.clone());
+
+        assert_eq!(results.len(), coords.len());
+        for (coord, res) in coords.into_iter().zip(results) {
+            let mut s = ProgramState::new(program.clone());
+            s.inputs = coord;
+            while s.return_state == ReturnState::ProducedOutput {
+                run_program(&mut s);
+            }
+            assert_eq!(res.outputs, s.outputs);
+            assert_eq!(res.steps, s.steps);
+            assert_eq!(res.end, JobEnd::Halted);
+        }
+
+        let mut batch = Batch::new(parse_program("3,0,1105,1,2"));
+        batch.max_steps = 100;
+        let results = batch.run(vec![vec![], vec![1]]);
+        assert_eq!(results[0].end, JobEnd::NeedMoreInput);
+        assert_eq!(results[1].end, JobEnd::StepLimit);
+        assert_eq!(results[1].steps, 100);
+    }
+}
@@ -0,0 +1,65 @@
+use intcode::cast::Recorder;
+use intcode::{parse_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!(
+        "usage: {} <program> <cast file> [input file] [speed=steps per second] [title=T] [addr=value]...",
+        name
+    );
+    std::process::exit(2);
+}
+
+// Runs an ASCII program on the text of the input file and records the
+// session for asciinema to play back.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut recorder = Recorder::new();
+    let mut files = Vec::new();
+    let mut patches = Vec::new();
+    for arg in &args[1..] {
+        match arg.split_once('=') {
+            Some(("speed", v)) => {
+                recorder.steps_per_second = v.parse().unwrap_or_else(|_| usage(&args[0]))
+            }
+            Some(("title", v)) => recorder.title = Some(v.to_owned()),
+            Some((addr, v)) => match (addr.parse::<usize>(), v.parse::<i64>()) {
+                (Ok(addr), Ok(v)) => patches.push((addr, v)),
+                _ => usage(&args[0]),
+            },
+            None => files.push(arg),
+        }
+    }
+    if files.len() < 2 || files.len() > 3 {
+        usage(&args[0]);
+    }
+
+    let program = parse_program(&fs::read_to_string(files[0]).expect("error reading file"));
+    let mut s = ProgramState::new(program);
+    for (addr, v) in patches {
+        s.write(addr, v);
+    }
+    if let Some(input) = files.get(2) {
+        s.inputs = fs::read_to_string(input)
+            .expect("error reading file")
+            .bytes()
+            .map(|b| b as i64)
+            .collect();
+    }
+
+    while s.return_state == ReturnState::ProducedOutput {
+        recorder.run_program(&mut s);
+    }
+    if s.return_state != ReturnState::Break {
+        eprintln!("stopped: {:?}", s.return_state);
+    }
+
+    let cast = recorder.cast(&s);
+    cast.save(files[1]).expect("error writing file");
+    println!(
+        "{} events over {:.1}s",
+        cast.events.len(),
+        cast.events.last().map_or(0.0, |e| e.time)
+    );
+}
@@ -0,0 +1,31 @@
+use intcode::compile::{compile, compile_asm};
+use std::env;
+use std::fs;
+
+// Compiles a source file to Intcode, or with --asm to the assembly listing.
+// Writes to stdout unless given an output file.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let asm = args.iter().any(|a| a == "--asm");
+    let files: Vec<&String> = args[1..].iter().filter(|a| *a != "--asm").collect();
+    if files.is_empty() || files.len() > 2 {
+        eprintln!("usage: {} <source> [output] [--asm]", args[0]);
+        std::process::exit(2);
+    }
+
+    let source = fs::read_to_string(files[0]).expect("error reading file");
+    let res = if asm {
+        compile_asm(&source)
+    } else {
+        compile(&source)
+    };
+    let text = res.unwrap_or_else(|e| {
+        eprintln!("{}: {}", files[0], e);
+        std::process::exit(1);
+    });
+
+    match files.get(1) {
+        Some(out) => fs::write(out, text).expect("error writing file"),
+        None => print!("{}", text),
+    }
+}
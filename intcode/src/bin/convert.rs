@@ -0,0 +1,28 @@
+use intcode::load::{read, save, Format};
+use std::env;
+
+// Converts a program between formats. The output format comes from the
+// --format flag, then the output extension, and defaults to comma text.
+// Either path can be - for stdin/stdout.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let usage = || -> ! {
+        eprintln!(
+            "usage: {} <input> <output> [--format comma|whitespace|binary]",
+            args[0]
+        );
+        std::process::exit(2);
+    };
+
+    let format = match args.len() {
+        3 => Format::from_extension(&args[2]).unwrap_or(Format::Comma),
+        5 if args[3] == "--format" => Format::from_name(&args[4]).unwrap_or_else(|| usage()),
+        _ => usage(),
+    };
+
+    let program = read(&args[1]).unwrap_or_else(|e| {
+        eprintln!("{}: {}", args[1], e);
+        std::process::exit(1);
+    });
+    save(&args[2], &program, format).expect("error writing program");
+}
@@ -0,0 +1,73 @@
+use intcode::coverage::Coverage;
+use intcode::{parse_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!("usage: {} run <program> <coverage file> [input]...", name);
+    eprintln!(
+        "       {} ascii <program> <coverage file> <text file>",
+        name
+    );
+    eprintln!("       {} merge <out file> <coverage file>...", name);
+    eprintln!("       {} report <program> <coverage file>...", name);
+    std::process::exit(2);
+}
+
+// Runs until the program halts or starves for input, recording into the
+// coverage file (merging with what is already there).
+fn record(program_file: &str, cov_file: &str, inputs: Vec<i64>) {
+    let input = fs::read_to_string(program_file).expect("error reading file");
+    let mut cov = if std::path::Path::new(cov_file).exists() {
+        Coverage::load(cov_file).expect("error reading coverage file")
+    } else {
+        Coverage::new()
+    };
+
+    let mut program = ProgramState::new(parse_program(&input));
+    program.inputs = inputs;
+    while program.return_state == ReturnState::ProducedOutput {
+        cov.run_program(&mut program);
+    }
+    cov.finish_run();
+
+    println!("outputs: {:?}", program.outputs);
+    cov.save(cov_file).expect("error writing coverage file");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        usage(&args[0]);
+    }
+
+    match args[1].as_str() {
+        "run" if args.len() >= 4 => {
+            let inputs = args[4..]
+                .iter()
+                .map(|a| a.parse::<i64>().expect("bad input value"))
+                .collect();
+            record(&args[2], &args[3], inputs);
+        }
+        "ascii" if args.len() == 5 => {
+            let text = fs::read_to_string(&args[4]).expect("error reading file");
+            record(&args[2], &args[3], text.bytes().map(|b| b as i64).collect());
+        }
+        "merge" => {
+            let mut cov = Coverage::new();
+            for f in &args[3..] {
+                cov.merge(&Coverage::load(f).expect("error reading coverage file"));
+            }
+            cov.save(&args[2]).expect("error writing coverage file");
+        }
+        "report" if args.len() >= 4 => {
+            let input = fs::read_to_string(&args[2]).expect("error reading file");
+            let mut cov = Coverage::new();
+            for f in &args[3..] {
+                cov.merge(&Coverage::load(f).expect("error reading coverage file"));
+            }
+            print!("{}", cov.annotate(&parse_program(&input)));
+        }
+        _ => usage(&args[0]),
+    }
+}
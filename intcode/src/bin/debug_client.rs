@@ -0,0 +1,34 @@
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::thread;
+
+// Forwards stdin lines to the debug server and prints everything it sends
+// back, replies and asynchronous events alike.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <socket>", args[0]);
+        std::process::exit(2);
+    }
+
+    let mut stream = UnixStream::connect(&args[1]).expect("error connecting to debug server");
+    let reader = BufReader::new(stream.try_clone().expect("error cloning stream"));
+
+    thread::spawn(move || {
+        for line in reader.lines() {
+            match line {
+                Ok(line) => println!("{}", line),
+                Err(_) => break,
+            }
+        }
+        std::process::exit(0);
+    });
+
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("error reading stdin");
+        if writeln!(stream, "{}", line).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use intcode::debug::DebugServer;
+use intcode::{parse_program, ProgramState};
+use std::env;
+use std::fs;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: {} <program> <socket> [input]...", args[0]);
+        std::process::exit(2);
+    }
+
+    let input = fs::read_to_string(&args[1]).expect("error reading file");
+
+    let mut program = ProgramState::new(parse_program(&input));
+    program.detect_loops();
+    for a in &args[3..] {
+        program
+            .inputs
+            .push(a.parse::<i64>().expect("bad input value"));
+    }
+
+    let mut server = DebugServer::bind(&args[2])
+        .expect("error binding socket")
+        .start_paused();
+
+    println!("listening on {}", args[2]);
+
+    server.serve(&mut program);
+
+    println!("pc: {}, outputs: {:?}", program.pc, program.outputs);
+}
@@ -0,0 +1,67 @@
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::time::Instant;
+
+// Compares the cost of a save-state on the breakout program (13/input.txt)
+// as a plain Vec copy of memory, which is what cloning used to cost, and as
+// a copy-on-write fork.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("usage: {} <program> [rounds]", args[0]);
+        std::process::exit(2);
+    }
+    let filename = &args[1];
+    let rounds = match args.get(2) {
+        Some(n) => n.parse::<usize>().expect("bad round count"),
+        None => 1000,
+    };
+
+    let input = fs::read_to_string(filename).expect("error reading file");
+
+    let mut state = ProgramState::new(parse_program(&input));
+    state.write(0, 2);
+
+    while state.return_state != ReturnState::NeedMoreInput
+        && state.return_state != ReturnState::Break
+    {
+        run_program(&mut state);
+    }
+
+    let flat = state.program.to_vec();
+
+    let start = Instant::now();
+    for _ in 0..rounds {
+        black_box(flat.clone());
+    }
+    let vec_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut forks = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+        forks.push(state.fork());
+    }
+    let fork_time = start.elapsed();
+
+    // Diverging after the fork is where the copying happens.
+    let start = Instant::now();
+    for f in &mut forks {
+        f.inputs.push(0);
+        run_program(f);
+    }
+    let run_time = start.elapsed();
+
+    println!(
+        "memory: {} cells, {} written pages",
+        flat.len(),
+        state.program.dirty_pages()
+    );
+    println!("vec clone: {:?} per copy", vec_time / rounds as u32);
+    println!("fork:      {:?} per copy", fork_time / rounds as u32);
+    println!(
+        "fork + run to next output: {:?} per copy",
+        run_time / rounds as u32
+    );
+}
@@ -0,0 +1,70 @@
+use intcode::heat::HeatMap;
+use intcode::{parse_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!(
+        "usage: {} <program> <timeline image> <strip image> [option]... [addr=value]... [input]...",
+        name
+    );
+    eprintln!("options: window=<steps> (default 1000), width=<pixels> (default 1024),");
+    eprintln!("         idle=<value> to feed whenever the program waits for input");
+    eprintln!("images are written as PGM if the name ends in .pgm, else as PPM");
+    std::process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        usage(&args[0]);
+    }
+
+    let input = fs::read_to_string(&args[1]).expect("error reading file");
+    let mut state = ProgramState::new(parse_program(&input));
+    let mut window = 1000;
+    let mut width = 1024;
+    let mut idle = None;
+    for a in &args[4..] {
+        match a.split_once('=') {
+            Some(("window", v)) => window = v.parse().expect("bad window"),
+            Some(("width", v)) => width = v.parse().expect("bad width"),
+            Some(("idle", v)) => idle = Some(v.parse::<i64>().expect("bad idle value")),
+            Some((addr, value)) => {
+                let addr = addr.parse::<usize>().expect("bad address");
+                state.write(addr, value.parse::<i64>().expect("bad value"));
+            }
+            None => state
+                .inputs
+                .push(a.parse::<i64>().expect("bad input value")),
+        }
+    }
+
+    // Runs until the program halts, or starves for input without an idle
+    // value to give it. 13/a plays breakout unattended with 0=2 idle=0.
+    let mut heat = HeatMap::new(window);
+    loop {
+        heat.run_program(&mut state);
+        match (&state.return_state, idle) {
+            (ReturnState::ProducedOutput, _) => {}
+            (ReturnState::NeedMoreInput, Some(v)) => state.inputs.push(v),
+            _ => break,
+        }
+    }
+
+    let (start, end) = heat.extent();
+    heat.timeline(start, end, width)
+        .save(&args[2])
+        .expect("error writing image");
+    heat.strip(start, end, width, 32)
+        .save(&args[3])
+        .expect("error writing image");
+    println!(
+        "steps: {}, windows: {}, addresses: {}..{}, state: {:?}",
+        state.steps,
+        heat.windows.len(),
+        start,
+        end,
+        state.return_state
+    );
+}
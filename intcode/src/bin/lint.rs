@@ -0,0 +1,30 @@
+use intcode::lint::{lint, Severity};
+use intcode::parse_program;
+use std::env;
+use std::fs;
+
+// Lints each program given, printing findings. Exits with 1 if any program
+// has errors.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <program>...", args[0]);
+        std::process::exit(2);
+    }
+
+    let mut errors = 0;
+    for filename in &args[1..] {
+        let input = fs::read_to_string(filename).expect("error reading file");
+        for finding in lint(&parse_program(&input)) {
+            if finding.severity == Severity::Error {
+                errors += 1;
+            }
+            println!("{}: {}", filename, finding);
+        }
+    }
+
+    if errors > 0 {
+        println!("{} errors", errors);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,73 @@
+use intcode::lockstep::Lockstep;
+use intcode::{parse_program, ProgramState};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!(
+        "usage: {} <program> <inputs a> <inputs b> [--jumps-only] [--inputs]",
+        name
+    );
+    eprintln!("inputs are comma separated numbers, or @file for a file's text as ASCII");
+    std::process::exit(2);
+}
+
+fn inputs(arg: &str) -> Vec<i64> {
+    match arg.strip_prefix('@') {
+        Some(file) => fs::read_to_string(file)
+            .expect("error reading file")
+            .bytes()
+            .map(|b| b as i64)
+            .collect(),
+        None => arg
+            .split(',')
+            .filter(|v| !v.trim().is_empty())
+            .map(|v| v.trim().parse::<i64>().expect("bad input value"))
+            .collect(),
+    }
+}
+
+// Runs the program on both inputs in lock step and reports where they part
+// ways. Exits with 1 if they do.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let flags: Vec<&String> = args
+        .iter()
+        .skip(1)
+        .filter(|a| a.starts_with("--"))
+        .collect();
+    let rest: Vec<&String> = args
+        .iter()
+        .skip(1)
+        .filter(|a| !a.starts_with("--"))
+        .collect();
+    if rest.len() != 3 {
+        usage(&args[0]);
+    }
+
+    let program = parse_program(&fs::read_to_string(rest[0]).expect("error reading file"));
+    let mut a = ProgramState::new(program.clone());
+    a.inputs = inputs(rest[1]);
+    let mut b = ProgramState::new(program);
+    b.inputs = inputs(rest[2]);
+
+    let mut l = Lockstep::new(a, b);
+    for flag in flags {
+        match flag.as_str() {
+            "--jumps-only" => l.compare_writes = false,
+            "--inputs" => l.compare_inputs = true,
+            _ => usage(&args[0]),
+        }
+    }
+
+    match l.run() {
+        Some(d) => {
+            print!("{}", l.report(&d));
+            std::process::exit(1);
+        }
+        None => println!(
+            "no difference in {} steps; a: {:?}, b: {:?}",
+            l.steps, l.a.return_state, l.b.return_state
+        ),
+    }
+}
@@ -0,0 +1,71 @@
+use intcode::diff::{diff, report, Snapshot};
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!(
+        "usage: {} snapshot <program> <snapshot file> [addr=value]... [input]...",
+        name
+    );
+    eprintln!("       {} diff <snapshot a> <snapshot b> [program]", name);
+    std::process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        usage(&args[0]);
+    }
+
+    match args[1].as_str() {
+        // Pokes memory (13/a needs 0=2 to play), then runs until the program
+        // halts or starves for input and saves where it stopped.
+        "snapshot" => {
+            let input = fs::read_to_string(&args[2]).expect("error reading file");
+            let mut state = ProgramState::new(parse_program(&input));
+            for a in &args[4..] {
+                match a.split_once('=') {
+                    Some((addr, value)) => {
+                        let addr = addr.parse::<usize>().expect("bad address");
+                        state.write(addr, value.parse::<i64>().expect("bad value"));
+                    }
+                    None => state
+                        .inputs
+                        .push(a.parse::<i64>().expect("bad input value")),
+                }
+            }
+            while state.return_state == ReturnState::ProducedOutput {
+                run_program(&mut state);
+            }
+            Snapshot::from_state(&state)
+                .save(&args[3])
+                .expect("error writing snapshot file");
+            println!(
+                "pc: {}, steps: {}, outputs: {}",
+                state.pc,
+                state.steps,
+                state.outputs.len()
+            );
+        }
+        "diff" => {
+            let a = Snapshot::load(&args[2]).expect("error reading snapshot file");
+            let b = Snapshot::load(&args[3]).expect("error reading snapshot file");
+            let program = args
+                .get(4)
+                .map(|f| parse_program(&fs::read_to_string(f).expect("error reading file")));
+
+            if a.pc != b.pc || a.relative_base != b.relative_base {
+                println!(
+                    "pc: {} -> {}, rb: {} -> {}",
+                    a.pc, b.pc, a.relative_base, b.relative_base
+                );
+            }
+            print!(
+                "{}",
+                report(&diff(&a.memory, &b.memory), program.as_deref())
+            );
+        }
+        _ => usage(&args[0]),
+    }
+}
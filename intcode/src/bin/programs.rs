@@ -0,0 +1,82 @@
+use intcode::catalog::{Catalog, Entry};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!(
+        "usage: {} [--manifest <file>] list | validate [name]... | run <name> [input]",
+        name
+    );
+    eprintln!("input is text, or @file for a file's contents; \\n stands for a line break");
+    std::process::exit(2);
+}
+
+fn entry<'a>(catalog: &'a Catalog, name: &str) -> &'a Entry {
+    catalog.get(name).unwrap_or_else(|| {
+        eprintln!("no program named {}", name);
+        std::process::exit(1);
+    })
+}
+
+// Lists, checks and runs the programs in the catalog by name.
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let mut manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/programs/manifest.txt").to_owned();
+    if args.len() > 2 && args[1] == "--manifest" {
+        manifest = args.remove(2);
+        args.remove(1);
+    }
+    if args.len() < 2 {
+        usage(&args[0]);
+    }
+    let catalog = Catalog::load(&manifest).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    match args[1].as_str() {
+        "list" => {
+            for e in &catalog.entries {
+                println!("{:<16} {:<8} {}", e.name, e.protocol.name(), e.description);
+            }
+        }
+        "validate" => {
+            let entries: Vec<&Entry> = if args.len() > 2 {
+                args[2..].iter().map(|n| entry(&catalog, n)).collect()
+            } else {
+                catalog.entries.iter().collect()
+            };
+            let mut failed = false;
+            for e in entries {
+                let problems = e.validate();
+                if problems.is_empty() {
+                    println!("{}: ok ({} examples)", e.name, e.examples.len());
+                }
+                for p in &problems {
+                    println!("{}: {}", e.name, p);
+                }
+                failed |= !problems.is_empty();
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        "run" if args.len() == 3 || args.len() == 4 => {
+            let input = match args.get(3) {
+                Some(arg) => match arg.strip_prefix('@') {
+                    Some(file) => fs::read_to_string(file).expect("error reading file"),
+                    None => arg.replace("\\n", "\n"),
+                },
+                None => String::new(),
+            };
+            match entry(&catalog, &args[2]).run(&input) {
+                Ok(output) => println!("{}", output.trim_end()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => usage(&args[0]),
+    }
+}
@@ -0,0 +1,160 @@
+use intcode::scan::{Condition, Pins, Scanner};
+use intcode::{parse_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+
+const HELP: &str = "\
+run                 run until the program halts or wants input
+input <value>..     queue input values
+ascii <text>        queue text and a newline as input
+poke <addr> <value> write memory
+find <value>        start a search for addresses holding value
+unknown             start a search over every address
+changed | unchanged | increased | decreased | equals <value>
+                    keep the candidates that match
+list [count]        show candidates and their values
+pin <addr> <value>  hold addr at value while running
+unpin <addr>
+quit";
+
+fn run(s: &mut ProgramState, pins: &Pins) {
+    let start = s.outputs.len();
+    s.return_state = ReturnState::ProducedOutput;
+    while s.return_state == ReturnState::ProducedOutput {
+        pins.run_program(s);
+    }
+
+    let outputs = &s.outputs[start..];
+    if outputs.iter().all(|v| *v > 0 && *v < 128) {
+        print!(
+            "{}",
+            outputs.iter().map(|v| *v as u8 as char).collect::<String>()
+        );
+    } else {
+        println!("{} outputs, last {:?}", outputs.len(), outputs.last());
+    }
+    println!("{:?} at pc {}", s.return_state, s.pc);
+}
+
+fn command(
+    line: &str,
+    s: &mut ProgramState,
+    scan: &mut Option<Scanner>,
+    pins: &mut Pins,
+) -> Result<(), String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let number = |i: usize| -> Result<i64, String> {
+        let w = words.get(i).ok_or("missing argument")?;
+        w.parse::<i64>().map_err(|_| format!("bad number {}", w))
+    };
+    let address = |i: usize| -> Result<usize, String> {
+        let addr = number(i)?;
+        if addr < 0 || addr as usize >= s.program.len() {
+            return Err(format!("bad address {}", addr));
+        }
+        Ok(addr as usize)
+    };
+
+    let condition = match words.first() {
+        Some(&"changed") => Some(Condition::Changed),
+        Some(&"unchanged") => Some(Condition::Unchanged),
+        Some(&"increased") => Some(Condition::Increased),
+        Some(&"decreased") => Some(Condition::Decreased),
+        Some(&"equals") => Some(Condition::Equals(number(1)?)),
+        _ => None,
+    };
+    if let Some(condition) = condition {
+        let scan = scan.as_mut().ok_or("no search started")?;
+        scan.narrow(&s.program, condition);
+        println!("{} candidates", scan.candidates.len());
+        return Ok(());
+    }
+
+    match words.first() {
+        Some(&"run") => run(s, pins),
+        Some(&"input") => {
+            for i in 1..words.len() {
+                s.inputs.push(number(i)?);
+            }
+        }
+        Some(&"ascii") => {
+            let text = line.trim_start()["ascii".len()..].trim_start();
+            s.inputs.extend(text.bytes().map(|b| b as i64));
+            s.inputs.push(10);
+        }
+        Some(&"poke") => {
+            let addr = address(1)?;
+            let value = number(2)?;
+            s.write(addr, value);
+        }
+        Some(&"find") => {
+            let found = Scanner::equals(&s.program, number(1)?);
+            println!("{} candidates", found.candidates.len());
+            *scan = Some(found);
+        }
+        Some(&"unknown") => {
+            let found = Scanner::unknown(&s.program);
+            println!("{} candidates", found.candidates.len());
+            *scan = Some(found);
+        }
+        Some(&"list") => {
+            let scan = scan.as_ref().ok_or("no search started")?;
+            let count = if words.len() > 1 {
+                number(1)? as usize
+            } else {
+                20
+            };
+            for (addr, value) in scan.candidates.iter().take(count) {
+                println!("{:>8}: {}", addr, value);
+            }
+            if scan.candidates.len() > count {
+                println!("... {} more", scan.candidates.len() - count);
+            }
+        }
+        Some(&"pin") => {
+            let addr = address(1)?;
+            pins.pin(addr, number(2)?);
+            pins.apply(s);
+        }
+        Some(&"unpin") => {
+            let addr = address(1)?;
+            if !pins.unpin(addr) {
+                return Err("address is not pinned".to_owned());
+            }
+        }
+        Some(&"help") => println!("{}", HELP),
+        None => {}
+        Some(w) => return Err(format!("unknown command {}, try help", w)),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: {} <program> [addr=value]...", args[0]);
+        std::process::exit(2);
+    }
+
+    let input = fs::read_to_string(&args[1]).expect("error reading file");
+    let mut s = ProgramState::new(parse_program(&input));
+    for a in &args[2..] {
+        let (addr, value) = a.split_once('=').expect("expected addr=value");
+        s.program[addr.parse::<usize>().expect("bad address")] =
+            value.parse::<i64>().expect("bad value");
+    }
+
+    let mut scan = None;
+    let mut pins = Pins::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.expect("error reading stdin");
+        if line.trim() == "quit" {
+            break;
+        }
+        if let Err(e) = command(&line, &mut s, &mut scan, &mut pins) {
+            println!("error: {}", e);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use intcode::session::Session;
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+
+fn usage(name: &str) -> ! {
+    eprintln!("usage: {} record <program> <session file> [input]...", name);
+    eprintln!("       {} replay <program> <session file>", name);
+    std::process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        usage(&args[0]);
+    }
+
+    let input = fs::read_to_string(&args[2]).expect("error reading file");
+    let program = parse_program(&input);
+
+    match args[1].as_str() {
+        "record" => {
+            let mut state = ProgramState::new(program);
+            for a in &args[4..] {
+                state
+                    .inputs
+                    .push(a.parse::<i64>().expect("bad input value"));
+            }
+            while state.return_state == ReturnState::ProducedOutput {
+                run_program(&mut state);
+            }
+            Session::from_state(&state)
+                .save(&args[3])
+                .expect("error writing session file");
+            println!("outputs: {:?}", state.outputs);
+        }
+        "replay" => {
+            let sessions = Session::load_all(&args[3]).expect("error reading session file");
+            for (i, session) in sessions.iter().enumerate() {
+                match session.replay(program.clone()) {
+                    Ok(state) => println!(
+                        "machine {} ok: {} inputs, {} outputs, {} steps",
+                        i,
+                        session.inputs.len(),
+                        state.outputs.len(),
+                        state.steps
+                    ),
+                    Err(mismatch) => {
+                        println!("machine {} mismatch: {:?}", i, mismatch);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        _ => usage(&args[0]),
+    }
+}
@@ -0,0 +1,283 @@
+// Call stack reconstruction for programs that follow the usual calling
+// convention: the caller writes the return address to [rb+0] and the
+// arguments to [rb+1..], jumps to the function, the function moves the
+// relative base past its frame with 109 n, and returns with 2106,0,0 after
+// moving it back.
+//
+// A taken jump counts as a call when [rb+0] holds the address of the
+// instruction after it, and a jump through relative slot 0 as a return to
+// whichever frame it lands in.
+
+use crate::registry::{jump_taken, operands, OperandError};
+use crate::{step_program, ProgramState};
+use std::fmt;
+
+// At most this many argument slots are shown per frame.
+pub const MAX_ARGS: i64 = 8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub entry: usize,
+    pub call_site: usize,
+    pub return_addr: usize,
+    // The caller's relative base; the return address is at base + 0.
+    pub base: i64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceFrame {
+    pub pc: usize,
+    // None for code outside any call.
+    pub function: Option<usize>,
+    pub base: i64,
+    pub args: Vec<i64>,
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.function {
+            Some(entry) => write!(
+                f,
+                "pc {} in fn {} base {} args {:?}",
+                self.pc, entry, self.base, self.args
+            ),
+            None => write!(f, "pc {} at top level", self.pc),
+        }
+    }
+}
+
+// A run that stopped on an instruction run_program would have panicked on.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fault {
+    pub pc: usize,
+    pub message: String,
+    pub backtrace: Vec<TraceFrame>,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "fault at {}: {}", self.pc, self.message)?;
+        for (i, frame) in self.backtrace.iter().enumerate() {
+            writeln!(f, "  #{} {}", i, frame)?;
+        }
+        Ok(())
+    }
+}
+
+// Why executing the instruction at pc would panic, if it would.
+pub fn check_step(s: &ProgramState) -> Result<(), String> {
+    match operands(s, usize::MAX) {
+        Ok(_) => Ok(()),
+        Err(OperandError::BadInstruction(n)) => Err(format!("illegal instruction {}", n)),
+        Err(OperandError::PastEnd(_)) => Err("instruction runs past the end of memory".to_owned()),
+        Err(OperandError::ImmediateWrite(i)) => {
+            Err(format!("operand {} writes in immediate mode", i + 1))
+        }
+        Err(OperandError::Address(i, addr)) => Err(format!("operand {} addresses {}", i + 1, addr)),
+        Err(OperandError::Overflow) => Err("relative address overflows".to_owned()),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CallStack {
+    pub frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack::default()
+    }
+
+    // Updates the stack after the instruction n at pc ran with relative
+    // base rb. taken is what jump_taken said before it ran, since a jump
+    // to pc + 3 looks the same as one that fell through.
+    pub fn observe(&mut self, pc: usize, n: i64, rb: i64, taken: bool, s: &ProgramState) {
+        if !taken {
+            return;
+        }
+
+        if n / 1000 % 10 == 2 && s.program[pc + 2] == 0 {
+            let target = s.pc;
+            let rb = s.relative_base;
+            if let Some(i) = self
+                .frames
+                .iter()
+                .rposition(|f| f.return_addr == target && f.base == rb)
+            {
+                self.frames.truncate(i);
+            }
+        } else if rb >= 0
+            && (rb as usize) < s.program.len()
+            && s.program[rb as usize] == (pc + 3) as i64
+        {
+            self.frames.push(Frame {
+                entry: s.pc,
+                call_site: pc,
+                return_addr: pc + 3,
+                base: rb,
+            });
+        }
+    }
+
+    // Innermost frame first. A frame's arguments are the slots between its
+    // base and the relative base its function moved to.
+    pub fn backtrace(&self, s: &ProgramState) -> Vec<TraceFrame> {
+        let mut res = Vec::new();
+        let mut pc = s.pc;
+        let mut rb = s.relative_base;
+
+        for frame in self.frames.iter().rev() {
+            let size = (rb - frame.base).clamp(0, MAX_ARGS);
+            let args = (1..=size)
+                .map(|i| (frame.base + i) as usize)
+                .filter(|a| *a < s.program.len())
+                .map(|a| s.program[a])
+                .collect();
+            res.push(TraceFrame {
+                pc,
+                function: Some(frame.entry),
+                base: frame.base,
+                args,
+            });
+            pc = frame.call_site;
+            rb = frame.base;
+        }
+        res.push(TraceFrame {
+            pc,
+            function: None,
+            base: rb,
+            args: vec![],
+        });
+
+        res
+    }
+
+    // Drop-in replacement for run_program that tracks calls, and stops with
+    // a fault report instead of panicking.
+    pub fn run_program(&mut self, s: &mut ProgramState) -> Result<(), Fault> {
+        while s.pc < s.program.len() {
+            if let Err(message) = check_step(s) {
+                return Err(Fault {
+                    pc: s.pc,
+                    message,
+                    backtrace: self.backtrace(s),
+                });
+            }
+
+            let pc = s.pc;
+            let n = s.program[pc];
+            let rb = s.relative_base;
+            let taken = jump_taken(s) == Some(true);
+            let yielded = step_program(s);
+            self.observe(pc, n, rb, taken, s);
+            if yielded {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::{parse_program, ReturnState};
+
+    #[test]
+    fn test_run() {
+        // main calls outer(5), outer calls inner(5 + 1, 7), inner outputs
+        // its arguments and then reads from a negative address.
+        let source = "
+                    arb stack
+                    add 5, 0, [rb+1]
+                    add back, 0, [rb+0]
+                    jt 1, outer
+            back:   hlt
+
+            outer:  arb 2
+                    add [rb-1], 1, [rb+1]
+                    add 7, 0, [rb+2]
+                    add ret, 0, [rb+0]
+                    jt 1, inner
+            ret:    arb -2
+                    jf 0, [rb+0]
+
+            inner:  arb 3
+                    out [rb-2]
+                    out [rb-1]
+                    add [rb-2], [-5], [rb-1]
+                    arb -3
+                    jf 0, [rb+0]
+
+            stack:  data 0
+        ";
+        let mut program = assemble(source).unwrap();
+        program.resize(1000, 0);
+
+        let mut s = ProgramState::new(program);
+        let mut calls = CallStack::new();
+        let mut res = Ok(());
+        while res.is_ok() && s.return_state == ReturnState::ProducedOutput {
+            res = calls.run_program(&mut s);
+        }
+        assert_eq!(s.outputs, [6, 7]);
+
+        let fault = res.unwrap_err();
+        assert_eq!(fault.message, "operand 2 addresses -5");
+        let trace = &fault.backtrace;
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace[0].pc, fault.pc);
+        assert_eq!(trace[0].args, [6, 7, 0]);
+        assert_eq!(trace[1].args[0], 5);
+        assert_eq!(trace[2].function, None);
+        assert!(fault.to_string().contains("#1 pc"));
+
+        // Returns pop frames again: a program that completes leaves an
+        // empty stack behind.
+        let mut program = assemble(&source.replace("[-5]", "[stack]")).unwrap();
+        program.resize(1000, 0);
+        let mut s = ProgramState::new(program);
+        let mut calls = CallStack::new();
+        while s.return_state == ReturnState::ProducedOutput {
+            calls.run_program(&mut s).unwrap();
+        }
+        assert_eq!(s.return_state, ReturnState::Break);
+        assert!(calls.frames.is_empty());
+
+        // Unpadded memory: a halt in the last cell runs, an instruction cut
+        // short by the end of memory is a fault.
+        let mut s = ProgramState::new(vec![99]);
+        CallStack::new().run_program(&mut s).unwrap();
+        assert_eq!(s.return_state, ReturnState::Break);
+        let mut s = ProgramState::new(vec![1101, 1, 1]);
+        let fault = CallStack::new().run_program(&mut s).unwrap_err();
+        assert_eq!(fault.message, "instruction runs past the end of memory");
+        assert_eq!(fault.backtrace.len(), 1);
+
+        // A jump with the relative base past the end of memory isn't a call.
+        let mut s = ProgramState::new(parse_program("109,2000000,1105,1,6,0,99"));
+        let mut calls = CallStack::new();
+        calls.run_program(&mut s).unwrap();
+        assert_eq!(s.return_state, ReturnState::Break);
+        assert!(calls.frames.is_empty());
+
+        // A call to the instruction right after the jump is still a call.
+        let mut program = parse_program("109,20,21101,9,0,0,1105,1,9,99");
+        program.resize(100, 0);
+        let mut s = ProgramState::new(program);
+        let mut calls = CallStack::new();
+        calls.run_program(&mut s).unwrap();
+        assert_eq!(calls.frames.len(), 1);
+        assert_eq!(calls.frames[0].entry, 9);
+
+        // Day 9's BOOST check runs through plenty of calls and returns.
+        let mut s = ProgramState::new(parse_program(include_str!("../../09/input")));
+        s.inputs.push(1);
+        let mut calls = CallStack::new();
+        while s.return_state == ReturnState::ProducedOutput {
+            calls.run_program(&mut s).unwrap();
+        }
+        assert!(calls.frames.is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+// Records ASCII sessions as asciicast v2, the format asciinema plays:
+//
+//   {"version": 2, "width": 80, "height": 24, "title": "21"}
+//   [0.012345, "o", "Input instructions:\r\n"]
+//   [0.013000, "i", "NOT A J\n"]
+//
+// Time is simulated, not measured: an event at step n is stamped
+// n / steps_per_second, so a recording comes out the same on every machine
+// and can be checked into tests. Output is grouped a line at a time, and
+// values outside ASCII, like 17's dust count, are printed as numbers.
+
+use crate::{step_program, ProgramState, ReturnState};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Event {
+    pub time: f64,
+    // 'o' for output, 'i' for input.
+    pub kind: char,
+    pub data: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cast {
+    pub width: usize,
+    pub height: usize,
+    pub title: Option<String>,
+    pub events: Vec<Event>,
+}
+
+fn quote(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            '\r' => res += "\\r",
+            '\t' => res += "\\t",
+            c if (c as u32) < 0x20 || c == '\x7f' => {
+                let _ = write!(res, "\\u{:04x}", c as u32);
+            }
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+// Reads the JSON string at the start of s, returning it and the rest.
+fn unquote(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut res = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((res, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => res.push('\n'),
+                'r' => res.push('\r'),
+                't' => res.push('\t'),
+                'u' => {
+                    let hex: String = (0..4).filter_map(|_| chars.next()).map(|c| c.1).collect();
+                    res.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => res.push(c),
+            },
+            c => res.push(c),
+        }
+    }
+    None
+}
+
+// The value of "key": <number> in a one-line JSON object.
+fn header_number(header: &str, key: &str) -> Option<usize> {
+    let start = header.find(&format!("\"{}\":", key))? + key.len() + 3;
+    let rest = header[start..].trim_start();
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+impl Cast {
+    pub fn to_text(&self) -> String {
+        let mut res = format!(
+            "{{\"version\": 2, \"width\": {}, \"height\": {}",
+            self.width, self.height
+        );
+        if let Some(title) = &self.title {
+            let _ = write!(res, ", \"title\": {}", quote(title));
+        }
+        res += "}\n";
+        for e in &self.events {
+            let _ = writeln!(res, "[{:.6}, \"{}\", {}]", e.time, e.kind, quote(&e.data));
+        }
+        res
+    }
+
+    // Reads casts as written by to_text; other asciicast v2 files work as
+    // long as each event is on one line.
+    pub fn parse(text: &str) -> Result<Cast, String> {
+        let mut lines = text.lines();
+        let header = lines.next().unwrap_or("");
+        if header_number(header, "version") != Some(2) {
+            return Err("not an asciicast v2 file".to_owned());
+        }
+        let title = header
+            .find("\"title\":")
+            .and_then(|i| unquote(header[i + 8..].trim_start()))
+            .map(|t| t.0);
+        let mut cast = Cast {
+            width: header_number(header, "width").unwrap_or(80),
+            height: header_number(header, "height").unwrap_or(24),
+            title,
+            events: Vec::new(),
+        };
+
+        for (i, line) in lines.enumerate() {
+            let bad = || format!("bad event on line {}: {}", i + 2, line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let inner = line
+                .trim()
+                .strip_prefix('[')
+                .and_then(|l| l.strip_suffix(']'))
+                .ok_or_else(bad)?;
+            let (time, rest) = inner.split_once(',').ok_or_else(bad)?;
+            let (kind, rest) = unquote(rest.trim_start()).ok_or_else(bad)?;
+            let rest = rest.trim_start().strip_prefix(',').ok_or_else(bad)?;
+            let (data, _) = unquote(rest.trim_start()).ok_or_else(bad)?;
+            cast.events.push(Event {
+                time: time.trim().parse().map_err(|_| bad())?,
+                kind: kind.chars().next().ok_or_else(bad)?,
+                data,
+            });
+        }
+        Ok(cast)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Cast> {
+        Cast::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // All output, as a terminal would have received it.
+    pub fn output(&self) -> String {
+        self.events
+            .iter()
+            .filter(|e| e.kind == 'o')
+            .map(|e| e.data.as_str())
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Recorder {
+    pub width: usize,
+    pub height: usize,
+    pub title: Option<String>,
+    pub steps_per_second: f64,
+    // Also show inputs as output, the way a terminal echoes typing.
+    pub echo_input: bool,
+    // (step, value) for each output, kept here so hosts can drain
+    // s.outputs as they like.
+    outputs: Vec<(u64, i64)>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder {
+            width: 80,
+            height: 24,
+            title: None,
+            steps_per_second: 10000.0,
+            echo_input: true,
+            outputs: Vec::new(),
+        }
+    }
+
+    // Drop-in replacement for run_program that notes when each output
+    // happened.
+    pub fn run_program(&mut self, s: &mut ProgramState) {
+        while s.pc < s.program.len() {
+            if step_program(s) {
+                if s.return_state == ReturnState::ProducedOutput {
+                    self.outputs.push((s.steps - 1, *s.outputs.last().unwrap()));
+                }
+                return;
+            }
+        }
+    }
+
+    // The recording so far. Inputs come from the machine's own history, so
+    // s must be the machine that was run.
+    pub fn cast(&self, s: &ProgramState) -> Cast {
+        let text = |v: i64| match v {
+            10 => "\r\n".to_owned(),
+            0..=127 => (v as u8 as char).to_string(),
+            _ => format!("{}\r\n", v),
+        };
+
+        // (step, is input, text), in the order things happened.
+        let mut pieces: Vec<(u64, bool, String)> = self
+            .outputs
+            .iter()
+            .map(|(step, v)| (*step, false, text(*v)))
+            .collect();
+        for (step, v) in s.input_steps.iter().zip(&s.inputs) {
+            pieces.push((*step, true, (*v as u8 as char).to_string()));
+        }
+        pieces.sort_by_key(|p| (p.0, p.1));
+
+        let mut events: Vec<Event> = Vec::new();
+        let mut open = false;
+        for (step, input, data) in pieces {
+            let kind = if input { 'i' } else { 'o' };
+            // Input lines echo into the output line by line as well.
+            let echo = input && self.echo_input;
+            match events.last_mut() {
+                Some(last) if open && last.kind == kind => last.data += &data,
+                _ => {
+                    let time = step as f64 / self.steps_per_second;
+                    events.push(Event { time, kind, data });
+                }
+            }
+            if echo {
+                let last = events.last().unwrap().clone();
+                if last.data.ends_with('\n') {
+                    events.push(Event {
+                        time: last.time,
+                        kind: 'o',
+                        data: last.data.replace('\n', "\r\n"),
+                    });
+                }
+            }
+            open = !events.last().unwrap().data.ends_with('\n');
+        }
+
+        Cast {
+            width: self.width,
+            height: self.height,
+            title: self.title.clone(),
+            events,
+        }
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Recorder {
+        Recorder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_run() {
+        // A springdroid that jumps too early and falls in a hole.
+        let mut s = ProgramState::new(parse_program(include_str!("../../21/input.txt")));
+        s.inputs = "NOT A J\nWALK\n".bytes().map(|b| b as i64).collect();
+        let mut recorder = Recorder::new();
+        recorder.title = Some("21 \"walk\"".to_owned());
+        while s.return_state == ReturnState::ProducedOutput {
+            recorder.run_program(&mut s);
+        }
+        assert_eq!(s.return_state, ReturnState::Break);
+
+        let cast = recorder.cast(&s);
+        assert_eq!(cast.events[0].data, "Input instructions:\r\n");
+        let inputs: Vec<&str> = cast
+            .events
+            .iter()
+            .filter(|e| e.kind == 'i')
+            .map(|e| e.data.as_str())
+            .collect();
+        assert_eq!(inputs, ["NOT A J\n", "WALK\n"]);
+        assert!(cast.events.windows(2).all(|w| w[0].time <= w[1].time));
+        let output = cast.output();
+        assert!(output.contains("NOT A J\r\nWALK\r\n"));
+        assert!(output.contains("Didn't make it across:"));
+        assert!(cast
+            .events
+            .iter()
+            .all(|e| e.kind == 'i' || !e.data.contains('\n') || e.data.ends_with("\r\n")));
+
+        let text = cast.to_text();
+        assert!(text.starts_with(
+            "{\"version\": 2, \"width\": 80, \"height\": 24, \"title\": \"21 \\\"walk\\\"\"}\n"
+        ));
+        assert!(text.contains(", \"i\", \"WALK\\n\"]\n"));
+        let parsed = Cast::parse(&text).unwrap();
+        assert_eq!(parsed.title, cast.title);
+        assert_eq!(parsed.events.len(), cast.events.len());
+        assert_eq!(parsed.output(), output);
+
+        // Numbers outside ASCII print as text.
+        let mut s = ProgramState::new(parse_program("104,72,104,105,104,10,104,19348,99"));
+        let mut recorder = Recorder::new();
+        recorder.steps_per_second = 1.0;
+        while s.return_state == ReturnState::ProducedOutput {
+            recorder.run_program(&mut s);
+        }
+        let cast = recorder.cast(&s);
+        assert_eq!(
+            cast.events,
+            [
+                Event {
+                    time: 0.0,
+                    kind: 'o',
+                    data: "Hi\r\n".to_owned()
+                },
+                Event {
+                    time: 3.0,
+                    kind: 'o',
+                    data: "19348\r\n".to_owned()
+                }
+            ]
+        );
+        assert!(Cast::parse("[1.0, \"o\", \"x\"]").is_err());
+    }
+}
@@ -0,0 +1,403 @@
+// Named programs with what it takes to run them, read from a manifest
+// (programs/manifest.txt):
+//
+//   intcode-catalog
+//   program breakout
+//   file breakout.txt
+//   protocol arcade
+//   patch 0=2
+//   example  -> score 12345
+//
+// Files are relative to the manifest; the catalog keeps its own copy of
+// each program next to it, one file per name. The protocol picks the adapter that
+// turns text into inputs and outputs into text:
+//
+//   numeric  comma separated values both ways
+//   ascii    text in, text out; values outside ASCII print as numbers
+//   arcade   joystick moves in, then plays by following the ball; prints
+//            the blocks left and the score
+//   droid    moves as north, south, west, east; statuses back as wall,
+//            moved, found
+//
+// An example gives an input and the output expected for it. Only the last
+// line of output that isn't blank is compared, trimmed, so an example can
+// check the answer at the end of a long transcript. In inputs \n stands
+// for a line break.
+
+use crate::framing::{Arcade, ArcadeEvent, Decoder, Tile};
+use crate::load::load;
+use crate::until::{run_until, Stop, Until};
+use crate::ProgramState;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "intcode-catalog";
+const MAX_STEPS: u64 = 1000000000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Protocol {
+    Numeric,
+    Ascii,
+    Arcade,
+    Droid,
+}
+
+impl Protocol {
+    pub fn from_name(name: &str) -> Option<Protocol> {
+        match name {
+            "numeric" => Some(Protocol::Numeric),
+            "ascii" => Some(Protocol::Ascii),
+            "arcade" => Some(Protocol::Arcade),
+            "droid" => Some(Protocol::Droid),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Numeric => "numeric",
+            Protocol::Ascii => "ascii",
+            Protocol::Arcade => "arcade",
+            Protocol::Droid => "droid",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Example {
+    pub input: String,
+    pub output: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub file: PathBuf,
+    pub protocol: Protocol,
+    pub description: String,
+    pub patches: Vec<(usize, i64)>,
+    pub examples: Vec<Example>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CatalogError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Catalog {
+    pub entries: Vec<Entry>,
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("\\n", "\n")
+}
+
+impl Catalog {
+    // Manifest text, with files relative to dir.
+    pub fn parse(text: &str, dir: &Path) -> Result<Catalog, CatalogError> {
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, l)| l.trim()) != Some(HEADER) {
+            return Err(CatalogError {
+                line: 1,
+                message: format!("expected {}", HEADER),
+            });
+        }
+
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut names = HashSet::new();
+        for (i, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| CatalogError {
+                line: i + 1,
+                message,
+            };
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let value = value.trim();
+
+            if key == "program" {
+                if value.is_empty() || !names.insert(value.to_owned()) {
+                    return Err(error(format!("bad or repeated name {:?}", value)));
+                }
+                entries.push(Entry {
+                    name: value.to_owned(),
+                    file: PathBuf::new(),
+                    protocol: Protocol::Numeric,
+                    description: String::new(),
+                    patches: Vec::new(),
+                    examples: Vec::new(),
+                });
+                continue;
+            }
+            let entry = entries
+                .last_mut()
+                .ok_or_else(|| error(format!("{} before the first program", key)))?;
+            match key {
+                "file" => entry.file = dir.join(value),
+                "protocol" => {
+                    entry.protocol = Protocol::from_name(value)
+                        .ok_or_else(|| error(format!("unknown protocol {}", value)))?
+                }
+                "description" => entry.description = value.to_owned(),
+                "patch" => {
+                    let patch = value
+                        .split_once('=')
+                        .and_then(|(a, v)| Some((a.trim().parse().ok()?, v.trim().parse().ok()?)))
+                        .ok_or_else(|| {
+                            error(format!("bad patch {}, expected addr=value", value))
+                        })?;
+                    entry.patches.push(patch);
+                }
+                "example" => {
+                    let (input, output) = value
+                        .split_once("->")
+                        .ok_or_else(|| error("example without ->".to_owned()))?;
+                    entry.examples.push(Example {
+                        input: unescape(input.trim()),
+                        output: output.trim().to_owned(),
+                    });
+                }
+                _ => return Err(error(format!("unknown key {}", key))),
+            }
+        }
+
+        for entry in &entries {
+            if entry.file.as_os_str().is_empty() {
+                return Err(CatalogError {
+                    line: 0,
+                    message: format!("{} has no file", entry.name),
+                });
+            }
+        }
+        Ok(Catalog { entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(manifest: P) -> io::Result<Catalog> {
+        let manifest = manifest.as_ref();
+        let text = fs::read_to_string(manifest)?;
+        let dir = manifest.parent().unwrap_or_else(|| Path::new(""));
+        Catalog::parse(&text, dir).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", manifest.display(), e),
+            )
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+}
+
+// Runs until the machine needs input or halts.
+fn run(s: &mut ProgramState) -> Result<Stop, String> {
+    let budget = MAX_STEPS.saturating_sub(s.steps);
+    match run_until(s, &Until::Steps(budget)) {
+        Stop::StepBudget => Err(format!("still running after {} steps", MAX_STEPS)),
+        Stop::InfiniteLoop { start, end } => Err(format!("stuck in a loop at {}..{}", start, end)),
+        stop => Ok(stop),
+    }
+}
+
+fn numbers(input: &str) -> Result<Vec<i64>, String> {
+    input
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(|_| format!("bad input value {:?}", v)))
+        .collect()
+}
+
+fn join(values: &[i64]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+fn numeric(s: &mut ProgramState, input: &str) -> Result<String, String> {
+    s.inputs.extend(numbers(input)?);
+    run(s)?;
+    Ok(join(&s.outputs))
+}
+
+fn ascii(s: &mut ProgramState, input: &str) -> Result<String, String> {
+    s.inputs.extend(input.bytes().map(|b| b as i64));
+    run(s)?;
+    let mut res = String::new();
+    for v in &s.outputs {
+        match v {
+            0..=127 => res.push(*v as u8 as char),
+            _ => res += &format!("{}\n", v),
+        }
+    }
+    Ok(res)
+}
+
+fn arcade(s: &mut ProgramState, input: &str) -> Result<String, String> {
+    s.inputs.extend(numbers(input)?);
+    let mut decoder = Decoder::<Arcade>::new();
+    let mut blocks = HashSet::new();
+    let (mut ball, mut paddle, mut score) = (0, 0, 0);
+    loop {
+        let stop = run(s)?;
+        for event in decoder.events(&s.outputs) {
+            match event.map_err(|e| e.to_string())? {
+                ArcadeEvent::Tile { x, y, tile } => {
+                    match tile {
+                        Tile::Ball => ball = x,
+                        Tile::Paddle => paddle = x,
+                        _ => {}
+                    }
+                    if tile == Tile::Block {
+                        blocks.insert((x, y));
+                    } else {
+                        blocks.remove(&(x, y));
+                    }
+                }
+                ArcadeEvent::Score(v) => score = v,
+            }
+        }
+        if stop == Stop::Halted {
+            break;
+        }
+        s.inputs.push((ball - paddle).signum());
+    }
+    Ok(format!("blocks {}\nscore {}", blocks.len(), score))
+}
+
+fn droid(s: &mut ProgramState, input: &str) -> Result<String, String> {
+    let mut statuses = Vec::new();
+    for step in input.split(',').map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        let command = match step {
+            "north" => 1,
+            "south" => 2,
+            "west" => 3,
+            "east" => 4,
+            _ => return Err(format!("bad move {:?}", step)),
+        };
+        s.inputs.push(command);
+        if run(s)? == Stop::Halted {
+            break;
+        }
+        statuses.push(match s.outputs.last() {
+            Some(0) => "wall",
+            Some(1) => "moved",
+            Some(2) => "found",
+            _ => return Err(format!("bad status after {}", step)),
+        });
+    }
+    Ok(statuses.join(","))
+}
+
+impl Entry {
+    // A machine ready to run, patches applied.
+    pub fn state(&self) -> Result<ProgramState, String> {
+        let program = load(&self.file).map_err(|e| format!("{}: {}", self.file.display(), e))?;
+        let mut s = ProgramState::new(program);
+        for (addr, v) in &self.patches {
+            if *addr >= s.program.len() {
+                return Err(format!("patch address {} out of range", addr));
+            }
+            s.write(*addr, *v);
+        }
+        Ok(s)
+    }
+
+    // Runs the program on the input through its protocol's adapter.
+    pub fn run(&self, input: &str) -> Result<String, String> {
+        let mut s = self.state()?;
+        match self.protocol {
+            Protocol::Numeric => numeric(&mut s, input),
+            Protocol::Ascii => ascii(&mut s, input),
+            Protocol::Arcade => arcade(&mut s, input),
+            Protocol::Droid => droid(&mut s, input),
+        }
+    }
+
+    // Problems with the entry: a file that doesn't load, or examples that
+    // don't give the expected output.
+    pub fn validate(&self) -> Vec<String> {
+        if let Err(e) = self.state() {
+            return vec![e];
+        }
+        let mut problems = Vec::new();
+        for example in &self.examples {
+            let last = |text: &str| {
+                text.lines()
+                    .rev()
+                    .find(|l| !l.trim().is_empty())
+                    .unwrap_or("")
+                    .trim()
+                    .to_owned()
+            };
+            match self.run(&example.input) {
+                Ok(output) if last(&output) == example.output => {}
+                Ok(output) => problems.push(format!(
+                    "{:?}: expected {}, got {}",
+                    example.input,
+                    example.output,
+                    last(&output)
+                )),
+                Err(e) => problems.push(format!("{:?}: {}", example.input, e)),
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+        let catalog = Catalog::load(dir.join("manifest.txt")).unwrap();
+        for entry in &catalog.entries {
+            assert_eq!(entry.validate(), Vec::<String>::new(), "{}", entry.name);
+            assert_eq!(entry.file, dir.join(format!("{}.txt", entry.name)));
+        }
+        let boost = catalog.get("boost").unwrap();
+        assert_eq!(boost.protocol, Protocol::Numeric);
+        assert!(boost.run("x").is_err());
+
+        let text = "intcode-catalog\nprogram echo\nfile echo.txt\nprotocol ascii\npatch 0=3\nexample hi\\n -> hi\n";
+        let catalog = Catalog::parse(text, Path::new("dir")).unwrap();
+        let echo = &catalog.entries[0];
+        assert_eq!(echo.file, Path::new("dir/echo.txt"));
+        assert_eq!(echo.patches, [(0, 3)]);
+        assert_eq!(echo.examples[0].input, "hi\n");
+        assert!(echo.validate()[0].contains("echo.txt"));
+
+        let error = |text: &str| Catalog::parse(text, Path::new("")).unwrap_err();
+        assert_eq!(error("programs\n").line, 1);
+        assert_eq!(
+            error("intcode-catalog\n\nprogram a\nprotocol morse\n"),
+            CatalogError {
+                line: 4,
+                message: "unknown protocol morse".to_owned()
+            }
+        );
+        assert_eq!(error("intcode-catalog\nfile a.txt\n").line, 2);
+        assert_eq!(
+            error("intcode-catalog\nprogram a\nfile a\nprogram a\n").line,
+            4
+        );
+        assert_eq!(
+            error("intcode-catalog\nprogram a\n").message,
+            "a has no file"
+        );
+    }
+}
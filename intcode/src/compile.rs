@@ -0,0 +1,790 @@
+// Compiler for a small language down to Intcode, by way of the assembler:
+//
+//   // Prints the factorials of the numbers read until a 0.
+//   fn fact(n) {
+//       if n < 2 { return 1; }
+//       return n * fact(n - 1);
+//   }
+//
+//   fn main() {
+//       var n = input();
+//       while n != 0 {
+//           output(fact(n));
+//           n = input();
+//       }
+//   }
+//
+// Values are integers; comparisons and ! give 0 or 1, and && and || stop
+// early. / and % truncate toward zero like Rust's, and i64::MIN / -1
+// overflows like any other arithmetic, but dividing by zero gives 0, so
+// a % 0 is a. 'c' is the character code of c. Names starting with __ are
+// kept for the compiler's own functions.
+//
+// Calls follow the convention callstack.rs expects: the caller puts the
+// return address at [rb+0] and the arguments at [rb+1..], the callee moves
+// rb past its frame of return address, parameters, locals and temporaries,
+// and leaves its result in the first parameter slot when it returns.
+
+use crate::asm::assemble;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: String) -> Result<T, CompileError> {
+    Err(CompileError { line, message })
+}
+
+// Signed division in terms of what Intcode has; appended when / or % is
+// used. Magnitudes are held as negative numbers, which reach i64::MIN where
+// positive ones can't, and nothing negates a value that could be i64::MIN.
+// __ndiv gives the quotient of two such magnitudes, negated: a / b is twice
+// a / 2b, plus one if that leaves b or more over, and b stops doubling once
+// 2b would pass a.
+const RUNTIME: &str = "
+fn __ndiv(a, b) {
+    if a > b { return 0; }
+    if b < -4611686018427387904 { return -1; }
+    if a > b + b { return -1; }
+    var q = __ndiv(a, b + b);
+    q = q + q;
+    if a - b <= q * -b { q = q - 1; }
+    return q;
+}
+fn __div(a, b) {
+    if b == 0 { return 0; }
+    var neg = 0;
+    if a > 0 {
+        a = -a;
+        neg = 1;
+    }
+    if b > 0 {
+        b = -b;
+        neg = !neg;
+    }
+    if neg { return __ndiv(a, b); }
+    return -__ndiv(a, b);
+}
+fn __mod(a, b) {
+    var p = __div(a, b) * b;
+    if p == a { return 0; }
+    return a - p;
+}
+";
+
+#[derive(Debug, PartialEq, Clone)]
+enum Tok {
+    Num(i64),
+    Ident(String),
+    Sym(&'static str),
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tok::Num(n) => write!(f, "{}", n),
+            Tok::Ident(s) => write!(f, "{}", s),
+            Tok::Sym(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+// Longest first, so <= is not read as < then =.
+const SYMBOLS: &[&str] = &[
+    "<=", ">=", "==", "!=", "&&", "||", "(", ")", "{", "}", ",", ";", "=", "+", "-", "*", "/", "%",
+    "<", ">", "!",
+];
+
+fn lex(source: &str) -> Result<Vec<(Tok, usize)>, CompileError> {
+    let mut res = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split("//").next().unwrap();
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap();
+            let len = if c.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                match rest[..len].parse() {
+                    Ok(n) => res.push((Tok::Num(n), line_no)),
+                    Err(_) => return error(line_no, format!("{} is too large", &rest[..len])),
+                }
+                len
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                res.push((Tok::Ident(rest[..len].to_owned()), line_no));
+                len
+            } else if c == '\'' {
+                let mut chars = rest[1..].chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), Some('\'')) => {
+                        res.push((Tok::Num(c as i64), line_no));
+                        2 + c.len_utf8()
+                    }
+                    _ => return error(line_no, "bad character literal".to_owned()),
+                }
+            } else {
+                match SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                    Some(s) => {
+                        res.push((Tok::Sym(s), line_no));
+                        s.len()
+                    }
+                    None => return error(line_no, format!("unexpected {:?}", c)),
+                }
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+    Ok(res)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Num(i64),
+    Var(String, usize),
+    Call(String, Vec<Expr>, usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Stmt {
+    Var(String, Expr, usize),
+    Assign(String, Expr, usize),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    line: usize,
+}
+
+struct Parser {
+    toks: Vec<(Tok, usize)>,
+    pos: usize,
+    divides: bool,
+}
+
+// Binary operators by precedence, loosest first.
+const LEVELS: &[&[(&str, BinOp)]] = &[
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[
+        ("<", BinOp::Lt),
+        ("<=", BinOp::Le),
+        (">", BinOp::Gt),
+        (">=", BinOp::Ge),
+    ],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Mod)],
+];
+
+impl Parser {
+    fn line(&self) -> usize {
+        match self.toks.get(self.pos).or_else(|| self.toks.last()) {
+            Some((_, line)) => *line,
+            None => 1,
+        }
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<Tok, CompileError> {
+        match self.toks.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => error(self.line(), "unexpected end of input".to_owned()),
+        }
+    }
+
+    fn eat(&mut self, sym: &str) -> bool {
+        match self.peek() {
+            Some(Tok::Sym(s)) if *s == sym => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, sym: &str) -> Result<(), CompileError> {
+        let line = self.line();
+        if self.eat(sym) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(t) => error(line, format!("expected {}, found {}", sym, t)),
+            None => error(line, format!("expected {} at end of input", sym)),
+        }
+    }
+
+    fn keyword(&mut self, word: &str) -> bool {
+        if self.peek() == Some(&Tok::Ident(word.to_owned())) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        let line = self.line();
+        match self.next()? {
+            Tok::Ident(name) => Ok(name),
+            t => error(line, format!("expected a name, found {}", t)),
+        }
+    }
+
+    fn program(&mut self) -> Result<Vec<Function>, CompileError> {
+        let mut res = Vec::new();
+        while self.peek().is_some() {
+            let line = self.line();
+            if !self.keyword("fn") {
+                return error(line, format!("expected fn, found {}", self.next()?));
+            }
+            let name = self.ident()?;
+            self.expect("(")?;
+            let mut params = Vec::new();
+            if !self.eat(")") {
+                loop {
+                    params.push(self.ident()?);
+                    if self.eat(")") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            let body = self.block()?;
+            res.push(Function {
+                name,
+                params,
+                body,
+                line,
+            });
+        }
+        Ok(res)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect("{")?;
+        let mut res = Vec::new();
+        while !self.eat("}") {
+            res.push(self.statement()?);
+        }
+        Ok(res)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        let line = self.line();
+        if self.keyword("var") {
+            let name = self.ident()?;
+            self.expect("=")?;
+            let value = self.expr(0)?;
+            self.expect(";")?;
+            return Ok(Stmt::Var(name, value, line));
+        }
+        if self.keyword("if") {
+            let cond = self.expr(0)?;
+            let then = self.block()?;
+            let otherwise = if !self.keyword("else") {
+                vec![]
+            } else if self.peek() == Some(&Tok::Ident("if".to_owned())) {
+                vec![self.statement()?]
+            } else {
+                self.block()?
+            };
+            return Ok(Stmt::If(cond, then, otherwise));
+        }
+        if self.keyword("while") {
+            let cond = self.expr(0)?;
+            return Ok(Stmt::While(cond, self.block()?));
+        }
+        if self.keyword("return") {
+            if self.eat(";") {
+                return Ok(Stmt::Return(None));
+            }
+            let value = self.expr(0)?;
+            self.expect(";")?;
+            return Ok(Stmt::Return(Some(value)));
+        }
+
+        if let (Some((Tok::Ident(name), _)), Some((Tok::Sym("="), _))) =
+            (self.toks.get(self.pos), self.toks.get(self.pos + 1))
+        {
+            let name = name.clone();
+            self.pos += 2;
+            let value = self.expr(0)?;
+            self.expect(";")?;
+            return Ok(Stmt::Assign(name, value, line));
+        }
+        let e = self.expr(0)?;
+        self.expect(";")?;
+        Ok(Stmt::Expr(e))
+    }
+
+    fn expr(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.expr(level + 1)?;
+        'outer: loop {
+            for (sym, op) in LEVELS[level] {
+                if self.eat(sym) {
+                    let rhs = self.expr(level + 1)?;
+                    if *op == BinOp::Div || *op == BinOp::Mod {
+                        self.divides = true;
+                    }
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            return Ok(match self.unary()? {
+                Expr::Num(n) => Expr::Num(-n),
+                e => Expr::Neg(Box::new(e)),
+            });
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, CompileError> {
+        let line = self.line();
+        match self.next()? {
+            Tok::Num(n) => Ok(Expr::Num(n)),
+            Tok::Ident(name) => {
+                if !self.eat("(") {
+                    return Ok(Expr::Var(name, line));
+                }
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.expr(0)?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::Call(name, args, line))
+            }
+            Tok::Sym("(") => {
+                let e = self.expr(0)?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            t => error(line, format!("unexpected {}", t)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Imm(i64),
+    Label(String),
+    // Frame slot: 0 is the return address, then parameters, locals and
+    // temporaries.
+    Slot(usize),
+    // [rb+n], where the next call's frame starts.
+    Out(usize),
+}
+
+enum Line {
+    Label(String),
+    Ins(&'static str, Vec<Operand>),
+}
+
+struct FnGen<'a> {
+    arity: &'a HashMap<String, usize>,
+    labels: &'a mut usize,
+    scopes: Vec<HashMap<String, usize>>,
+    next_slot: usize,
+    frame: usize,
+    lines: Vec<Line>,
+    ret: String,
+}
+
+impl<'a> FnGen<'a> {
+    fn label(&mut self) -> String {
+        *self.labels += 1;
+        format!("_L{}", self.labels)
+    }
+
+    fn emit(&mut self, mnemonic: &'static str, operands: Vec<Operand>) {
+        self.lines.push(Line::Ins(mnemonic, operands));
+    }
+
+    fn place(&mut self, label: &str) {
+        self.lines.push(Line::Label(label.to_owned()));
+    }
+
+    fn copy(&mut self, v: Operand, slot: usize) {
+        match v {
+            Operand::Slot(s) if s == slot => {}
+            v => self.emit("add", vec![v, Operand::Imm(0), Operand::Slot(slot)]),
+        }
+    }
+
+    fn temp(&mut self) -> usize {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.frame = self.frame.max(self.next_slot);
+        slot
+    }
+
+    fn lookup(&self, name: &str, line: usize) -> Result<usize, CompileError> {
+        match self.scopes.iter().rev().find_map(|s| s.get(name)) {
+            Some(slot) => Ok(*slot),
+            None => error(line, format!("unknown variable {}", name)),
+        }
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        let mark = self.next_slot;
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        self.scopes.pop();
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        use Operand::*;
+        // Temporaries live until the end of the statement.
+        let mark = self.next_slot;
+        match stmt {
+            Stmt::Var(name, value, line) => {
+                let v = self.expr(value)?;
+                self.next_slot = mark;
+                let slot = self.temp();
+                let scope = self.scopes.last_mut().unwrap();
+                if scope.insert(name.clone(), slot).is_some() {
+                    return error(*line, format!("{} is already declared here", name));
+                }
+                self.copy(v, slot);
+                return Ok(());
+            }
+            Stmt::Assign(name, value, line) => {
+                let slot = self.lookup(name, *line)?;
+                let v = self.expr(value)?;
+                self.copy(v, slot);
+            }
+            Stmt::If(cond, then, otherwise) => {
+                let c = self.expr(cond)?;
+                self.next_slot = mark;
+                let (other, end) = (self.label(), self.label());
+                self.emit("jf", vec![c, Label(other.clone())]);
+                self.block(then)?;
+                self.emit("jt", vec![Imm(1), Label(end.clone())]);
+                self.place(&other);
+                self.block(otherwise)?;
+                self.place(&end);
+            }
+            Stmt::While(cond, body) => {
+                let (top, end) = (self.label(), self.label());
+                self.place(&top);
+                let c = self.expr(cond)?;
+                self.next_slot = mark;
+                self.emit("jf", vec![c, Label(end.clone())]);
+                self.block(body)?;
+                self.emit("jt", vec![Imm(1), Label(top)]);
+                self.place(&end);
+            }
+            Stmt::Return(value) => {
+                let v = match value {
+                    Some(e) => self.expr(e)?,
+                    None => Imm(0),
+                };
+                self.copy(v, 1);
+                self.emit("jt", vec![Imm(1), Label(self.ret.clone())]);
+            }
+            Stmt::Expr(e) => {
+                self.expr(e)?;
+            }
+        }
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    // Evaluates e, returning where its value is. Temporaries above the
+    // value's own are free again afterwards.
+    fn expr(&mut self, e: &Expr) -> Result<Operand, CompileError> {
+        use Operand::*;
+        let mark = self.next_slot;
+        let res = match e {
+            Expr::Num(n) => return Ok(Imm(*n)),
+            Expr::Var(name, line) => return Ok(Slot(self.lookup(name, *line)?)),
+            Expr::Neg(e) => {
+                let v = self.expr(e)?;
+                self.next_slot = mark;
+                let t = self.temp();
+                self.emit("mul", vec![v, Imm(-1), Slot(t)]);
+                t
+            }
+            Expr::Not(e) => {
+                let v = self.expr(e)?;
+                self.next_slot = mark;
+                let t = self.temp();
+                self.emit("eq", vec![v, Imm(0), Slot(t)]);
+                t
+            }
+            Expr::Binary(op @ (BinOp::And | BinOp::Or), a, b) => {
+                let t = self.temp();
+                let end = self.label();
+                let short = if *op == BinOp::And { 0 } else { 1 };
+                self.emit("add", vec![Imm(short), Imm(0), Slot(t)]);
+                let a = self.expr(a)?;
+                let jump = if *op == BinOp::And { "jf" } else { "jt" };
+                self.emit(jump, vec![a, Label(end.clone())]);
+                let b = self.expr(b)?;
+                self.emit("eq", vec![b, Imm(0), Slot(t)]);
+                self.emit("eq", vec![Slot(t), Imm(0), Slot(t)]);
+                self.place(&end);
+                self.next_slot = mark + 1;
+                t
+            }
+            Expr::Binary(op @ (BinOp::Div | BinOp::Mod), a, b) => {
+                let name = if *op == BinOp::Div { "__div" } else { "__mod" };
+                let call = Expr::Call(name.to_owned(), vec![(**a).clone(), (**b).clone()], 0);
+                return self.expr(&call);
+            }
+            Expr::Binary(op, a, b) => {
+                let a = self.expr(a)?;
+                let b = self.expr(b)?;
+                self.next_slot = mark;
+                let t = self.temp();
+                let (mnemonic, x, y, negate) = match op {
+                    BinOp::Add => ("add", a, b, false),
+                    BinOp::Sub => {
+                        let neg = match b {
+                            Imm(n) => Imm(-n),
+                            b => {
+                                let u = self.temp();
+                                self.emit("mul", vec![b, Imm(-1), Slot(u)]);
+                                Slot(u)
+                            }
+                        };
+                        ("add", a, neg, false)
+                    }
+                    BinOp::Mul => ("mul", a, b, false),
+                    BinOp::Lt => ("lt", a, b, false),
+                    BinOp::Gt => ("lt", b, a, false),
+                    BinOp::Le => ("lt", b, a, true),
+                    BinOp::Ge => ("lt", a, b, true),
+                    BinOp::Eq => ("eq", a, b, false),
+                    BinOp::Ne => ("eq", a, b, true),
+                    _ => unreachable!(),
+                };
+                self.emit(mnemonic, vec![x, y, Slot(t)]);
+                if negate {
+                    self.emit("eq", vec![Slot(t), Imm(0), Slot(t)]);
+                }
+                t
+            }
+            Expr::Call(name, args, line) => match (name.as_str(), args.len()) {
+                ("input", 0) => {
+                    let t = self.temp();
+                    self.emit("in", vec![Slot(t)]);
+                    t
+                }
+                ("output", 1) => {
+                    let v = self.expr(&args[0])?;
+                    self.emit("out", vec![v]);
+                    self.next_slot = mark;
+                    return Ok(Imm(0));
+                }
+                ("input", _) | ("output", _) => {
+                    return error(*line, format!("wrong number of arguments to {}", name))
+                }
+                _ => {
+                    match self.arity.get(name) {
+                        Some(n) if *n == args.len() => {}
+                        Some(n) => {
+                            return error(
+                                *line,
+                                format!("{} takes {} arguments, got {}", name, n, args.len()),
+                            )
+                        }
+                        None => return error(*line, format!("unknown function {}", name)),
+                    }
+                    // Arguments are all evaluated before any is stored, as
+                    // calls among them would overwrite the outgoing frame.
+                    let mut values = Vec::new();
+                    for a in args {
+                        values.push(self.expr(a)?);
+                    }
+                    for (i, v) in values.into_iter().enumerate() {
+                        self.emit("add", vec![v, Imm(0), Out(i + 1)]);
+                    }
+                    let back = self.label();
+                    self.emit("add", vec![Label(back.clone()), Imm(0), Out(0)]);
+                    self.emit("jt", vec![Imm(1), Label(format!("fn_{}", name))]);
+                    self.place(&back);
+                    self.next_slot = mark;
+                    let t = self.temp();
+                    self.emit("add", vec![Out(1), Imm(0), Slot(t)]);
+                    t
+                }
+            },
+        };
+        Ok(Slot(res))
+    }
+
+    fn render(&self, f: &Function, out: &mut String) {
+        let frame = self.frame;
+        let operand = |o: &Operand| match o {
+            Operand::Imm(n) => n.to_string(),
+            Operand::Label(l) => l.clone(),
+            Operand::Slot(n) => format!("[rb-{}]", frame - n),
+            Operand::Out(n) => format!("[rb+{}]", n),
+        };
+
+        *out += &format!("\n# {}({})\n", f.name, f.params.join(", "));
+        *out += &format!("fn_{}: arb {}\n", f.name, frame);
+        for line in &self.lines {
+            match line {
+                Line::Label(l) => *out += &format!("{}:\n", l),
+                Line::Ins(m, ops) => {
+                    let ops: Vec<String> = ops.iter().map(operand).collect();
+                    *out += &format!("    {} {}\n", m, ops.join(", "));
+                }
+            }
+        }
+        *out += &format!("{}: arb -{}\n    jf 0, [rb+0]\n", self.ret, frame);
+    }
+}
+
+// The assembly listing for source.
+pub fn compile_asm(source: &str) -> Result<String, CompileError> {
+    let toks = lex(source)?;
+    for (tok, line) in &toks {
+        if let Tok::Ident(name) = tok {
+            if name.starts_with("__") {
+                return error(*line, format!("{} is reserved", name));
+            }
+        }
+    }
+    let mut parser = Parser {
+        toks,
+        pos: 0,
+        divides: false,
+    };
+    let mut functions = parser.program()?;
+    if parser.divides {
+        let mut runtime = Parser {
+            toks: lex(RUNTIME).unwrap(),
+            pos: 0,
+            divides: false,
+        };
+        functions.extend(runtime.program().unwrap());
+    }
+
+    let mut arity = HashMap::new();
+    for f in &functions {
+        if f.name == "input" || f.name == "output" {
+            return error(f.line, format!("{} is built in", f.name));
+        }
+        if arity.insert(f.name.clone(), f.params.len()).is_some() {
+            return error(f.line, format!("function {} defined twice", f.name));
+        }
+    }
+    match arity.get("main") {
+        Some(0) => {}
+        Some(_) => return error(1, "main takes no arguments".to_owned()),
+        None => return error(1, "no main function".to_owned()),
+    }
+
+    let mut out =
+        String::from("    arb stack\n    add _start, 0, [rb+0]\n    jt 1, fn_main\n_start: hlt\n");
+    let mut labels = 0;
+    for f in &functions {
+        let mut scope = HashMap::new();
+        for (i, p) in f.params.iter().enumerate() {
+            if scope.insert(p.clone(), i + 1).is_some() {
+                return error(f.line, format!("parameter {} repeated", p));
+            }
+        }
+        let slots = f.params.len() + 1;
+        let mut gen = FnGen {
+            arity: &arity,
+            labels: &mut labels,
+            scopes: vec![scope],
+            next_slot: slots,
+            // Room for the result even without parameters.
+            frame: slots.max(2),
+            lines: Vec::new(),
+            ret: format!("fn_{}.ret", f.name),
+        };
+        gen.block(&f.body)?;
+        // Falling off the end returns 0.
+        gen.emit(
+            "add",
+            vec![Operand::Imm(0), Operand::Imm(0), Operand::Slot(1)],
+        );
+        gen.render(f, &mut out);
+    }
+    out += "\nstack: data 0\n";
+    Ok(out)
+}
+
+// The program as comma separated Intcode, ready for parse_program.
+pub fn compile(source: &str) -> Result<String, CompileError> {
+    let listing = compile_asm(source)?;
+    let program = assemble(&listing).map_err(|e| CompileError {
+        line: 0,
+        message: format!("internal error: {}", e),
+    })?;
+    let values: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+    Ok(format!("{}\n", values.join(",")))
+}
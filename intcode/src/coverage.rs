@@ -0,0 +1,272 @@
+// Records which instructions ran and which way conditional jumps went,
+// across any number of runs. Coverage files are plain text:
+//
+//   intcode-coverage
+//   runs <n>
+//   hit <addr> <count>
+//   branch <addr> <taken> <not taken>
+
+use crate::disasm::{disassemble, trimmed_len};
+use crate::registry::jump_taken;
+use crate::{step_program, Opcode, ProgramState};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Coverage {
+    pub runs: u64,
+    pub hits: BTreeMap<usize, u64>,
+    pub branches: BTreeMap<usize, (u64, u64)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Summary {
+    pub instructions: usize,
+    pub executed: usize,
+    pub branch_outcomes: usize,
+    pub branch_outcomes_seen: usize,
+}
+
+impl Summary {
+    pub fn percent(&self) -> f64 {
+        if self.instructions == 0 {
+            return 0.0;
+        }
+        100.0 * self.executed as f64 / self.instructions as f64
+    }
+
+    pub fn branch_percent(&self) -> f64 {
+        if self.branch_outcomes == 0 {
+            return 0.0;
+        }
+        100.0 * self.branch_outcomes_seen as f64 / self.branch_outcomes as f64
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    // Drop-in replacement for run_program that records as it goes. Call
+    // finish_run once per run to keep the run count right.
+    pub fn run_program(&mut self, s: &mut ProgramState) {
+        while s.pc < s.program.len() {
+            let pc = s.pc;
+            let steps = s.steps;
+            // Decided up front, as a jump to pc + 3 still counts as taken.
+            let taken = jump_taken(s);
+
+            let yielded = step_program(s);
+
+            if s.steps == steps {
+                // Stalled on input; nothing ran.
+                return;
+            }
+            *self.hits.entry(pc).or_insert(0) += 1;
+
+            if let Some(taken) = taken {
+                let branch = self.branches.entry(pc).or_insert((0, 0));
+                if taken {
+                    branch.0 += 1;
+                } else {
+                    branch.1 += 1;
+                }
+            }
+
+            if yielded {
+                return;
+            }
+        }
+    }
+
+    pub fn finish_run(&mut self) {
+        self.runs += 1;
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        self.runs += other.runs;
+        for (addr, n) in &other.hits {
+            *self.hits.entry(*addr).or_insert(0) += n;
+        }
+        for (addr, (taken, not_taken)) in &other.branches {
+            let branch = self.branches.entry(*addr).or_insert((0, 0));
+            branch.0 += taken;
+            branch.1 += not_taken;
+        }
+    }
+
+    pub fn summary(&self, program: &[i64]) -> Summary {
+        let listing = self.listing(program);
+        let mut summary = Summary {
+            instructions: 0,
+            executed: 0,
+            branch_outcomes: 0,
+            branch_outcomes_seen: 0,
+        };
+        for ins in &listing {
+            let op = match ins.op {
+                Some(op) => op,
+                None => continue,
+            };
+            summary.instructions += 1;
+            if self.hits.contains_key(&ins.addr) {
+                summary.executed += 1;
+            }
+            if op == Opcode::JumpIfTrue || op == Opcode::JumpIfFalse {
+                summary.branch_outcomes += 2;
+                if let Some((taken, not_taken)) = self.branches.get(&ins.addr) {
+                    summary.branch_outcomes_seen +=
+                        (*taken > 0) as usize + (*not_taken > 0) as usize;
+                }
+            }
+        }
+        summary
+    }
+
+    // Disassembly with a hit count per instruction and taken/not taken
+    // counts for conditional jumps, followed by the summary.
+    pub fn annotate(&self, program: &[i64]) -> String {
+        let mut res = String::new();
+
+        for ins in self.listing(program) {
+            let count = match self.hits.get(&ins.addr) {
+                Some(n) => n.to_string(),
+                None if ins.op.is_some() => "-".to_owned(),
+                None => "".to_owned(),
+            };
+            let _ = write!(res, "{:>8} {:>6}  {}", count, ins.addr, ins);
+            if let Some((taken, not_taken)) = self.branches.get(&ins.addr) {
+                let _ = write!(res, "    taken {} / not taken {}", taken, not_taken);
+            }
+            res.push('\n');
+        }
+
+        let summary = self.summary(program);
+        let _ = writeln!(
+            res,
+            "\n{} runs, {}/{} instructions ({:.1}%), {}/{} branch outcomes ({:.1}%)",
+            self.runs,
+            summary.executed,
+            summary.instructions,
+            summary.percent(),
+            summary.branch_outcomes_seen,
+            summary.branch_outcomes,
+            summary.branch_percent()
+        );
+
+        res
+    }
+
+    fn listing(&self, program: &[i64]) -> Vec<crate::disasm::Instruction> {
+        let last_hit = self.hits.keys().next_back().map(|a| a + 1).unwrap_or(0);
+        let len = trimmed_len(program).max(last_hit).min(program.len());
+        let starts: BTreeSet<usize> = self.hits.keys().cloned().collect();
+        disassemble(&program[0..len], &starts)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut res = format!("intcode-coverage\nruns {}\n", self.runs);
+        for (addr, n) in &self.hits {
+            let _ = writeln!(res, "hit {} {}", addr, n);
+        }
+        for (addr, (taken, not_taken)) in &self.branches {
+            let _ = writeln!(res, "branch {} {} {}", addr, taken, not_taken);
+        }
+        fs::write(path, res)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Coverage> {
+        let text = fs::read_to_string(path)?;
+        let bad = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad coverage line: {}", line),
+            )
+        };
+
+        let mut lines = text.lines();
+        if lines.next() != Some("intcode-coverage") {
+            return Err(bad("missing intcode-coverage header"));
+        }
+
+        let mut cov = Coverage::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let nums: Vec<u64> = words
+                .iter()
+                .skip(1)
+                .map(|w| w.parse::<u64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| bad(line))?;
+            match (words.first(), nums.len()) {
+                (Some(&"runs"), 1) => cov.runs = nums[0],
+                (Some(&"hit"), 2) => {
+                    cov.hits.insert(nums[0] as usize, nums[1]);
+                }
+                (Some(&"branch"), 3) => {
+                    cov.branches.insert(nums[0] as usize, (nums[1], nums[2]));
+                }
+                (None, _) => {}
+                _ => return Err(bad(line)),
+            }
+        }
+        Ok(cov)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, ReturnState};
+
+    fn run(cov: &mut Coverage, program: &str, input: i64) {
+        let mut s = ProgramState::new(parse_program(program));
+        s.inputs.push(input);
+        while s.return_state == ReturnState::ProducedOutput {
+            cov.run_program(&mut s);
+        }
+        cov.finish_run();
+    }
+
+    #[test]
+    fn test_run() {
+        // Halts straight away if the input is 8, outputs 0 first otherwise.
+        let program = "3,12,1008,12,8,12,1005,12,11,104,0,99,0";
+
+        let mut a = Coverage::new();
+        run(&mut a, program, 8);
+        assert_eq!(a.hits.get(&9), None);
+        assert_eq!(a.branches[&6], (1, 0));
+
+        let mut b = Coverage::new();
+        run(&mut b, program, 7);
+        assert_eq!(b.branches[&6], (0, 1));
+
+        let path = std::env::temp_dir().join(format!("intcode-cov-{}", std::process::id()));
+        b.save(&path).unwrap();
+        let b = Coverage::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        a.merge(&b);
+        assert_eq!(a.runs, 2);
+        assert_eq!(a.hits[&0], 2);
+        assert_eq!(a.branches[&6], (1, 1));
+
+        let summary = a.summary(&parse_program(program));
+        assert_eq!(summary.executed, summary.instructions);
+        assert_eq!(summary.branch_outcomes_seen, 2);
+
+        let report = a.annotate(&parse_program(program));
+        assert!(report.contains("taken 1 / not taken 1"));
+        assert!(report.contains("(100.0%)"));
+
+        // A jump to the next instruction still counts as taken.
+        let mut c = Coverage::new();
+        run(&mut c, "3,9,1005,9,5,104,1,99,0,0", 1);
+        assert_eq!(c.branches[&2], (1, 0));
+    }
+}
@@ -0,0 +1,496 @@
+// Line based remote debug protocol served over a unix domain socket.
+//
+// Every command line gets exactly one reply, starting with "ok" or "err".
+// State changes are pushed to all attached clients as lines starting with
+// '*', and can arrive interleaved with replies.
+//
+//   halt                   stop before the next instruction
+//   continue               resume execution
+//   step [n]               execute n instructions (default 1) while halted
+//   read <addr> [count]    ok <value> ...
+//   write <addr> <value>.. overwrite memory starting at addr
+//   break <addr>           add a breakpoint
+//   delete <addr>          remove a breakpoint
+//   breakpoints            ok <addr> ...
+//   input <value>..        append to the machine's input queue
+//   regs                   ok pc=.. rb=.. steps=.. state=..
+//   backtrace              ok #0 pc=.. fn=.. base=.. args=a,b.. #1 ..
+//   detach                 close this connection
+//   quit                   end the debug session
+//
+//   *stopped reason=<halt|breakpoint|step> pc=..
+//   *stopped reason=loop pc=.. start=.. end=..
+//   *running
+//   *output <value>
+//   *need-input pc=..
+//   *halted pc=..
+//   *fault pc=.. <message>     the next instruction would crash the VM
+
+use crate::callstack::{check_step, CallStack};
+use crate::registry::jump_taken;
+use crate::{step_program, ProgramState, ReturnState};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: u64 = 4096;
+
+enum Incoming {
+    Connected(usize, UnixStream),
+    Line(usize, String),
+    Disconnected(usize),
+}
+
+pub struct DebugServer {
+    path: PathBuf,
+    incoming: Receiver<Incoming>,
+    clients: Vec<(usize, UnixStream)>,
+    breakpoints: BTreeSet<usize>,
+    paused: bool,
+    step_budget: u64,
+    skip_break: bool,
+    quit: bool,
+    calls: CallStack,
+}
+
+impl DebugServer {
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<DebugServer> {
+        let path = path.as_ref().to_owned();
+        let listener = UnixListener::bind(&path)?;
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let writer = match stream.try_clone() {
+                    Ok(writer) => writer,
+                    Err(_) => continue,
+                };
+                if tx.send(Incoming::Connected(id, writer)).is_err() {
+                    return;
+                }
+
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for line in BufReader::new(stream).lines() {
+                        match line {
+                            Ok(line) => {
+                                if tx.send(Incoming::Line(id, line)).is_err() {
+                                    return;
+                                }
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                    let _ = tx.send(Incoming::Disconnected(id));
+                });
+            }
+        });
+
+        Ok(DebugServer {
+            path,
+            incoming: rx,
+            clients: Vec::new(),
+            breakpoints: BTreeSet::new(),
+            paused: false,
+            step_budget: 0,
+            skip_break: false,
+            quit: false,
+            calls: CallStack::new(),
+        })
+    }
+
+    // Halts the machine before its first instruction, so a client can set
+    // breakpoints before anything runs.
+    pub fn start_paused(mut self) -> DebugServer {
+        self.paused = true;
+        self
+    }
+
+    pub fn is_quit(&self) -> bool {
+        self.quit
+    }
+
+    // Drop-in replacement for run_program: executes until the machine
+    // yields, honouring breakpoints and blocking while halted.
+    pub fn run_program(&mut self, s: &mut ProgramState) {
+        let mut since_poll = 0;
+
+        while s.pc < s.program.len() {
+            let waiting = self.paused && self.step_budget == 0;
+            if waiting || since_poll >= POLL_INTERVAL {
+                self.poll(s, waiting);
+                since_poll = 0;
+            }
+            if self.quit {
+                return;
+            }
+            if self.paused && self.step_budget == 0 {
+                continue;
+            }
+
+            if !self.skip_break && self.breakpoints.contains(&s.pc) {
+                self.paused = true;
+                self.step_budget = 0;
+                self.broadcast(&format!("*stopped reason=breakpoint pc={}", s.pc));
+                continue;
+            }
+            self.skip_break = false;
+
+            if let Err(message) = check_step(s) {
+                self.paused = true;
+                self.step_budget = 0;
+                self.broadcast(&format!("*fault pc={} {}", s.pc, message));
+                continue;
+            }
+
+            let (pc, n, rb) = (s.pc, s.program[s.pc], s.relative_base);
+            let taken = jump_taken(s) == Some(true);
+            let yielded = step_program(s);
+            self.calls.observe(pc, n, rb, taken, s);
+            since_poll += 1;
+
+            if yielded {
+                match s.return_state {
+                    ReturnState::ProducedOutput => {
+                        let value = *s.outputs.last().unwrap();
+                        self.broadcast(&format!("*output {}", value));
+                    }
+                    ReturnState::NeedMoreInput => {
+                        self.broadcast(&format!("*need-input pc={}", s.pc));
+                    }
+                    ReturnState::Break => {
+                        self.broadcast(&format!("*halted pc={}", s.pc));
+                    }
+                    ReturnState::InfiniteLoop { start, end } => {
+                        self.paused = true;
+                        self.step_budget = 0;
+                        self.broadcast(&format!(
+                            "*stopped reason=loop pc={} start={} end={}",
+                            s.pc, start, end
+                        ));
+                    }
+                }
+            }
+
+            // A machine that stopped itself, like on a loop, has already
+            // used up the budget.
+            if self.paused && self.step_budget > 0 {
+                self.step_budget -= 1;
+                if self.step_budget == 0 {
+                    self.broadcast(&format!("*stopped reason=step pc={}", s.pc));
+                }
+            }
+
+            if yielded {
+                return;
+            }
+        }
+    }
+
+    // Runs the machine as a standalone debug target until a client quits.
+    // Waits for injected input when starved, and keeps a halted machine
+    // around for inspection.
+    pub fn serve(&mut self, s: &mut ProgramState) {
+        while !self.quit {
+            let starved =
+                s.return_state == ReturnState::NeedMoreInput && s.input_counter >= s.inputs.len();
+            if s.return_state == ReturnState::Break || starved {
+                self.poll(s, true);
+            } else {
+                self.run_program(s);
+            }
+        }
+    }
+
+    fn poll(&mut self, s: &mut ProgramState, block: bool) {
+        let mut next = if block {
+            match self.incoming.recv_timeout(Duration::from_millis(100)) {
+                Ok(msg) => Some(msg),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.quit = true;
+                    None
+                }
+            }
+        } else {
+            self.try_next()
+        };
+
+        while let Some(msg) = next {
+            match msg {
+                Incoming::Connected(id, stream) => self.clients.push((id, stream)),
+                Incoming::Disconnected(id) => self.clients.retain(|c| c.0 != id),
+                Incoming::Line(id, line) => {
+                    let reply = match self.handle(s, &line) {
+                        Ok(reply) => reply,
+                        Err(e) => format!("err {}", e),
+                    };
+                    self.send(id, &reply);
+                    if line.trim() == "detach" {
+                        self.clients.retain(|c| c.0 != id);
+                    }
+                }
+            }
+            next = self.try_next();
+        }
+    }
+
+    fn try_next(&mut self) -> Option<Incoming> {
+        match self.incoming.try_recv() {
+            Ok(msg) => Some(msg),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.quit = true;
+                None
+            }
+        }
+    }
+
+    fn handle(&mut self, s: &mut ProgramState, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            return Err("empty command".to_owned());
+        }
+        let args = &words[1..];
+
+        match words[0] {
+            "halt" => {
+                if !self.paused {
+                    self.paused = true;
+                    self.step_budget = 0;
+                    self.broadcast(&format!("*stopped reason=halt pc={}", s.pc));
+                }
+                Ok("ok".to_owned())
+            }
+            "continue" => {
+                if s.return_state == ReturnState::Break {
+                    return Err("machine has halted".to_owned());
+                }
+                if self.paused {
+                    self.paused = false;
+                    self.step_budget = 0;
+                    self.skip_break = true;
+                    self.broadcast("*running");
+                }
+                Ok("ok".to_owned())
+            }
+            "step" => {
+                if s.return_state == ReturnState::Break {
+                    return Err("machine has halted".to_owned());
+                }
+                if !self.paused {
+                    return Err("machine is running".to_owned());
+                }
+                let n = match args.first() {
+                    Some(n) => parse_count(n)? as u64,
+                    None => 1,
+                };
+                self.step_budget = n;
+                self.skip_break = true;
+                Ok("ok".to_owned())
+            }
+            "read" => {
+                let addr = parse_address(s, args.first())?;
+                let count = match args.get(1) {
+                    Some(n) => parse_count(n)?,
+                    None => 1,
+                };
+                let end = addr.saturating_add(count).min(s.program.len());
+                let values: Vec<String> = s
+                    .program
+                    .range(addr, end)
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect();
+                Ok(format!("ok {}", values.join(" ")))
+            }
+            "write" => {
+                let addr = parse_address(s, args.first())?;
+                let values = args[1..]
+                    .iter()
+                    .map(|v| parse_number(v))
+                    .collect::<Result<Vec<i64>, String>>()?;
+                if addr + values.len() > s.program.len() {
+                    return Err("write past end of memory".to_owned());
+                }
+                for (i, v) in values.iter().enumerate() {
+                    s.write(addr + i, *v);
+                }
+                Ok("ok".to_owned())
+            }
+            "break" => {
+                let addr = parse_address(s, args.first())?;
+                self.breakpoints.insert(addr);
+                Ok("ok".to_owned())
+            }
+            "delete" => {
+                let addr = parse_address(s, args.first())?;
+                if self.breakpoints.remove(&addr) {
+                    Ok("ok".to_owned())
+                } else {
+                    Err(format!("no breakpoint at {}", addr))
+                }
+            }
+            "breakpoints" => {
+                let addrs: Vec<String> = self.breakpoints.iter().map(|a| a.to_string()).collect();
+                Ok(format!("ok {}", addrs.join(" ")).trim_end().to_owned())
+            }
+            "input" => {
+                for v in args {
+                    s.inputs.push(parse_number(v)?);
+                }
+                Ok("ok".to_owned())
+            }
+            "regs" => {
+                let state = if s.return_state == ReturnState::Break {
+                    "halted"
+                } else if self.paused {
+                    "paused"
+                } else if s.return_state == ReturnState::NeedMoreInput
+                    && s.input_counter >= s.inputs.len()
+                {
+                    "need-input"
+                } else {
+                    "running"
+                };
+                Ok(format!(
+                    "ok pc={} rb={} steps={} state={}",
+                    s.pc, s.relative_base, s.steps, state
+                ))
+            }
+            "backtrace" => {
+                let mut res = "ok".to_owned();
+                for (i, frame) in self.calls.backtrace(s).iter().enumerate() {
+                    res += &format!(" #{} pc={}", i, frame.pc);
+                    if let Some(entry) = frame.function {
+                        let args: Vec<String> = frame.args.iter().map(|a| a.to_string()).collect();
+                        res +=
+                            &format!(" fn={} base={} args={}", entry, frame.base, args.join(","));
+                    }
+                }
+                Ok(res)
+            }
+            "detach" => Ok("ok".to_owned()),
+            "quit" => {
+                self.quit = true;
+                Ok("ok".to_owned())
+            }
+            cmd => Err(format!("unknown command {}", cmd)),
+        }
+    }
+
+    fn send(&mut self, id: usize, line: &str) {
+        let mut failed = false;
+        if let Some((_, stream)) = self.clients.iter_mut().find(|c| c.0 == id) {
+            failed = writeln!(stream, "{}", line).is_err();
+        }
+        if failed {
+            self.clients.retain(|c| c.0 != id);
+        }
+    }
+
+    fn broadcast(&mut self, line: &str) {
+        self.clients
+            .retain(|(_, stream)| writeln!(&*stream, "{}", line).is_ok());
+    }
+}
+
+impl Drop for DebugServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn parse_number(word: &str) -> Result<i64, String> {
+    word.parse::<i64>()
+        .map_err(|_| format!("bad number {}", word))
+}
+
+fn parse_count(word: &str) -> Result<usize, String> {
+    let n = parse_number(word)?;
+    if n < 0 {
+        return Err(format!("bad count {}", n));
+    }
+    Ok(n as usize)
+}
+
+fn parse_address(s: &ProgramState, word: Option<&&str>) -> Result<usize, String> {
+    let word = word.ok_or_else(|| "missing address".to_owned())?;
+    let addr = parse_number(word)?;
+    if addr < 0 || addr as usize >= s.program.len() {
+        return Err(format!("address {} out of range", addr));
+    }
+    Ok(addr as usize)
+}
+
+// Reference client. Replies are matched up with commands; events that
+// arrive in between are queued and handed out by next_event.
+pub struct DebugClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    events: Vec<String>,
+}
+
+impl DebugClient {
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<DebugClient> {
+        let writer = UnixStream::connect(path)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(DebugClient {
+            reader,
+            writer,
+            events: Vec::new(),
+        })
+    }
+
+    pub fn command(&mut self, line: &str) -> io::Result<Result<String, String>> {
+        writeln!(self.writer, "{}", line)?;
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with('*') {
+                self.events.push(line);
+            } else if line == "ok" {
+                return Ok(Ok(String::new()));
+            } else if let Some(rest) = line.strip_prefix("ok ") {
+                return Ok(Ok(rest.to_owned()));
+            } else if let Some(rest) = line.strip_prefix("err ") {
+                return Ok(Err(rest.to_owned()));
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected reply: {}", line),
+                ));
+            }
+        }
+    }
+
+    pub fn next_event(&mut self) -> io::Result<String> {
+        if !self.events.is_empty() {
+            return Ok(self.events.remove(0));
+        }
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with('*') {
+                return Ok(line);
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "debug server closed the connection",
+            ));
+        }
+        Ok(line.trim_end().to_owned())
+    }
+}
@@ -0,0 +1,222 @@
+// Memory diffs between two machine states, for finding where a program keeps
+// its variables. Snapshot files hold a machine's registers and memory in
+// plain text, with trailing zeroes trimmed:
+//
+//   intcode-snapshot
+//   pc <pc>
+//   rb <relative base>
+//   steps <steps>
+//   memory <value>,<value>,...
+
+use crate::disasm::{decode_at, trimmed_len, Instruction};
+use crate::memory::Memory;
+use crate::{Mode, ProgramState};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub pc: usize,
+    pub relative_base: i64,
+    pub steps: u64,
+    pub memory: Memory,
+}
+
+// A run of contiguous changed cells.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChangedRange {
+    pub start: usize,
+    pub old: Vec<i64>,
+    pub new: Vec<i64>,
+}
+
+impl ChangedRange {
+    pub fn end(&self) -> usize {
+        self.start + self.old.len()
+    }
+}
+
+impl Snapshot {
+    pub fn from_state(s: &ProgramState) -> Snapshot {
+        Snapshot {
+            pc: s.pc,
+            relative_base: s.relative_base,
+            steps: s.steps,
+            memory: s.program.clone(),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let memory = self.memory.range(0, trimmed_len(&self.memory.to_vec()));
+        let values: Vec<String> = memory.iter().map(|v| v.to_string()).collect();
+        let res = format!(
+            "intcode-snapshot\npc {}\nrb {}\nsteps {}\nmemory {}\n",
+            self.pc,
+            self.relative_base,
+            self.steps,
+            values.join(",")
+        );
+        fs::write(path, res)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Snapshot> {
+        let text = fs::read_to_string(path)?;
+        let bad = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad snapshot line: {}", line),
+            )
+        };
+
+        let mut lines = text.lines();
+        if lines.next() != Some("intcode-snapshot") {
+            return Err(bad("missing intcode-snapshot header"));
+        }
+
+        let mut snapshot = Snapshot {
+            pc: 0,
+            relative_base: 0,
+            steps: 0,
+            memory: Memory::from(vec![]),
+        };
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "pc" => snapshot.pc = value.parse().map_err(|_| bad(line))?,
+                "rb" => snapshot.relative_base = value.parse().map_err(|_| bad(line))?,
+                "steps" => snapshot.steps = value.parse().map_err(|_| bad(line))?,
+                "memory" => {
+                    let mut memory = value
+                        .split(',')
+                        .filter(|v| !v.is_empty())
+                        .map(|v| v.parse::<i64>())
+                        .collect::<Result<Vec<i64>, _>>()
+                        .map_err(|_| bad("memory"))?;
+                    memory.resize(memory.len().max(1000000), 0);
+                    snapshot.memory = Memory::from(memory);
+                }
+                "" => {}
+                _ => return Err(bad(line)),
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+pub fn diff(a: &Memory, b: &Memory) -> Vec<ChangedRange> {
+    let mut res: Vec<ChangedRange> = Vec::new();
+    let value = |m: &Memory, i: usize| if i < m.len() { m[i] } else { 0 };
+
+    for addr in a.differences(b) {
+        match res.last_mut() {
+            Some(range) if range.end() == addr => {
+                range.old.push(value(a, addr));
+                range.new.push(value(b, addr));
+            }
+            _ => res.push(ChangedRange {
+                start: addr,
+                old: vec![value(a, addr)],
+                new: vec![value(b, addr)],
+            }),
+        }
+    }
+
+    res
+}
+
+// Instructions that name each address as a position mode operand, found by
+// a linear sweep over the program. Relative mode operands depend on the
+// relative base at run time and are not included.
+pub fn references(program: &[i64]) -> BTreeMap<usize, Vec<Instruction>> {
+    let mut res: BTreeMap<usize, Vec<Instruction>> = BTreeMap::new();
+    let len = trimmed_len(program);
+    let mut addr = 0;
+
+    while addr < len {
+        let ins = decode_at(program, addr);
+        addr += ins.len();
+        for i in 1..ins.len() {
+            if ins.modes[i - 1] == Mode::Position && ins.raw[i] >= 0 {
+                let refs = res.entry(ins.raw[i] as usize).or_default();
+                if refs.last().map(|r| r.addr) != Some(ins.addr) {
+                    refs.push(ins.clone());
+                }
+            }
+        }
+    }
+
+    res
+}
+
+// One line per range, followed by one line per changed cell. With a program,
+// each cell also lists the instructions that reference it.
+pub fn report(ranges: &[ChangedRange], program: Option<&[i64]>) -> String {
+    let refs = program.map(references).unwrap_or_default();
+    let mut res = String::new();
+
+    for range in ranges {
+        if range.old.len() == 1 {
+            let _ = writeln!(res, "{}", range.start);
+        } else {
+            let _ = writeln!(
+                res,
+                "{}..{} ({} cells)",
+                range.start,
+                range.end() - 1,
+                range.old.len()
+            );
+        }
+        for (i, (old, new)) in range.old.iter().zip(&range.new).enumerate() {
+            let addr = range.start + i;
+            let _ = writeln!(res, "  {:>8}: {} -> {}", addr, old, new);
+            for ins in refs.get(&addr).into_iter().flatten() {
+                let _ = writeln!(res, "            {:>6}  {}", ins.addr, ins);
+            }
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, run_program};
+
+    #[test]
+    fn test_run() {
+        // Reads a value into [17], doubles it into [18] and counts reads in
+        // [19].
+        let program = parse_program("3,17,1002,17,2,18,1001,19,1,19,4,18,1105,1,0,0,0,0,0,0");
+        let mut s = ProgramState::new(program.clone());
+        s.inputs.push(4);
+        run_program(&mut s);
+        let before = Snapshot::from_state(&s);
+        s.inputs.push(5);
+        run_program(&mut s);
+
+        let ranges = diff(&before.memory, &s.program);
+        assert_eq!(
+            ranges,
+            [ChangedRange {
+                start: 17,
+                old: vec![4, 8, 1],
+                new: vec![5, 10, 2]
+            }]
+        );
+
+        let path = std::env::temp_dir().join(format!("intcode-snapshot-{}", std::process::id()));
+        before.save(&path).unwrap();
+        let loaded = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, before);
+        assert_eq!(diff(&loaded.memory, &s.program), ranges);
+
+        let text = report(&ranges, Some(&program));
+        assert!(text.starts_with("17..19 (3 cells)\n"));
+        assert!(text.ends_with("19: 1 -> 2\n                 6  add [19], 1, [19]\n"));
+    }
+}
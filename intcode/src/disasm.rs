@@ -0,0 +1,239 @@
+use crate::registry::Registry;
+use crate::{Mode, Opcode};
+use std::collections::BTreeSet;
+use std::fmt;
+
+pub fn mnemonic(op: Opcode) -> &'static str {
+    match op {
+        Opcode::Addition => "add",
+        Opcode::Multiplication => "mul",
+        Opcode::Input => "in",
+        Opcode::Output => "out",
+        Opcode::JumpIfTrue => "jt",
+        Opcode::JumpIfFalse => "jf",
+        Opcode::LessThan => "lt",
+        Opcode::Equals => "eq",
+        Opcode::AdjustRelativeBase => "arb",
+        Opcode::Break => "hlt",
+    }
+}
+
+pub fn param_count(op: Opcode) -> usize {
+    match op {
+        Opcode::Addition | Opcode::Multiplication | Opcode::LessThan | Opcode::Equals => 3,
+        Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+        Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+        Opcode::Break => 0,
+    }
+}
+
+// Index of the operand the instruction writes to, if any.
+pub fn write_param(op: Opcode) -> Option<usize> {
+    match op {
+        Opcode::Input => Some(0),
+        Opcode::Addition | Opcode::Multiplication | Opcode::LessThan | Opcode::Equals => Some(2),
+        _ => None,
+    }
+}
+
+// Like parse_op, but returns None instead of panicking on cells that are
+// not valid instructions.
+pub fn decode(n: i64) -> Option<(Opcode, [Mode; 3])> {
+    if n < 0 {
+        return None;
+    }
+    let op = match n % 100 {
+        1 => Opcode::Addition,
+        2 => Opcode::Multiplication,
+        3 => Opcode::Input,
+        4 => Opcode::Output,
+        5 => Opcode::JumpIfTrue,
+        6 => Opcode::JumpIfFalse,
+        7 => Opcode::LessThan,
+        8 => Opcode::Equals,
+        9 => Opcode::AdjustRelativeBase,
+        99 => Opcode::Break,
+        _ => return None,
+    };
+    if n >= 100000 {
+        return None;
+    }
+    let mut modes = [Mode::Position; 3];
+    let mut digits = n / 100;
+    for m in &mut modes {
+        *m = match digits % 10 {
+            0 => Mode::Position,
+            1 => Mode::Immediate,
+            2 => Mode::Relative,
+            _ => return None,
+        };
+        digits /= 10;
+    }
+    Some((op, modes))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction {
+    pub addr: usize,
+    // None for a cell that does not decode, shown as data.
+    pub op: Option<Opcode>,
+    // Set for registry opcodes too, which have no Opcode.
+    pub mnemonic: Option<String>,
+    pub modes: [Mode; 3],
+    pub raw: Vec<i64>,
+}
+
+impl Instruction {
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+}
+
+fn fmt_param(f: &mut fmt::Formatter, mode: Mode, val: i64) -> fmt::Result {
+    match mode {
+        Mode::Position => write!(f, "[{}]", val),
+        Mode::Immediate => write!(f, "{}", val),
+        Mode::Relative if val < 0 => write!(f, "[rb{}]", val),
+        Mode::Relative => write!(f, "[rb+{}]", val),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.mnemonic {
+            Some(m) => write!(f, "{}", m)?,
+            None => return write!(f, "data {}", self.raw[0]),
+        }
+        for i in 1..self.raw.len() {
+            write!(f, "{}", if i == 1 { " " } else { ", " })?;
+            fmt_param(f, self.modes[i - 1], self.raw[i])?;
+        }
+        Ok(())
+    }
+}
+
+fn data(program: &[i64], addr: usize) -> Instruction {
+    Instruction {
+        addr,
+        op: None,
+        mnemonic: None,
+        modes: [Mode::Position; 3],
+        raw: vec![program[addr]],
+    }
+}
+
+pub fn decode_at(program: &[i64], addr: usize) -> Instruction {
+    if let Some((op, modes)) = decode(program[addr]) {
+        let end = addr + 1 + param_count(op);
+        if end <= program.len() {
+            return Instruction {
+                addr,
+                op: Some(op),
+                mnemonic: Some(mnemonic(op).to_owned()),
+                modes,
+                raw: program[addr..end].to_vec(),
+            };
+        }
+    }
+    data(program, addr)
+}
+
+// decode_at for the opcodes in a registry, built-in or not.
+pub fn decode_at_with(registry: &Registry, program: &[i64], addr: usize) -> Instruction {
+    if let Some((def, modes)) = registry.decode(program[addr]) {
+        let end = addr + 1 + def.params.len();
+        if end <= program.len() {
+            return Instruction {
+                addr,
+                op: decode(program[addr]).map(|d| d.0),
+                mnemonic: Some(def.mnemonic.clone()),
+                modes,
+                raw: program[addr..end].to_vec(),
+            };
+        }
+    }
+    data(program, addr)
+}
+
+// Length of the program without the zero padding parse_program adds.
+pub fn trimmed_len(program: &[i64]) -> usize {
+    match program.iter().rposition(|v| *v != 0) {
+        Some(i) => i + 1,
+        None => 0,
+    }
+}
+
+// Linear sweep over program. Addresses in starts are known instruction
+// starts (from execution, say); an instruction that would swallow one is
+// shown as data instead, so the sweep stays in step with real code.
+pub fn disassemble(program: &[i64], starts: &BTreeSet<usize>) -> Vec<Instruction> {
+    sweep(program, starts, decode_at)
+}
+
+pub fn disassemble_with(
+    registry: &Registry,
+    program: &[i64],
+    starts: &BTreeSet<usize>,
+) -> Vec<Instruction> {
+    sweep(program, starts, |program, addr| {
+        decode_at_with(registry, program, addr)
+    })
+}
+
+fn sweep<F: Fn(&[i64], usize) -> Instruction>(
+    program: &[i64],
+    starts: &BTreeSet<usize>,
+    decode_at: F,
+) -> Vec<Instruction> {
+    let mut res = Vec::new();
+    let mut addr = 0;
+
+    while addr < program.len() {
+        let mut ins = decode_at(program, addr);
+        if starts.range(addr + 1..addr + ins.len()).next().is_some() {
+            ins = data(program, addr);
+        }
+        addr += ins.len();
+        res.push(ins);
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let program = [109, 1, 204, -1, 1001, 100, 1, 100, 1006, 101, 0, 99, 123];
+        let listing: Vec<String> = disassemble(&program, &BTreeSet::new())
+            .iter()
+            .map(|i| format!("{}: {}", i.addr, i))
+            .collect();
+        assert_eq!(
+            listing,
+            [
+                "0: arb 1",
+                "2: out [rb-1]",
+                "4: add [100], 1, [100]",
+                "8: jf [101], 0",
+                "11: hlt",
+                "12: data 123",
+            ]
+        );
+
+        let mut starts = BTreeSet::new();
+        starts.insert(6);
+        let listing = disassemble(&program, &starts);
+        assert_eq!(listing[2].op, None);
+        assert_eq!(listing[4].addr, 6);
+
+        assert_eq!(trimmed_len(&[1, 0, 2, 0, 0]), 3);
+        assert!(decode(3201).is_none());
+    }
+}
@@ -0,0 +1,253 @@
+// C API for embedding the VM, declared in include/intcode.h. Built into
+// the cdylib along with the rest of the crate.
+//
+// Machines are opaque pointers from intcode_create or intcode_snapshot and
+// must be freed with intcode_destroy. Every function other than create
+// expects a pointer from one of those that has not been destroyed, and
+// pointer arguments must be valid for the access; nothing is checked.
+// A fault inside the VM, like a bad opcode, doesn't cross into C: the
+// machine reports INTCODE_ERROR from then on. Each instruction is checked
+// before it runs, so those faults never panic; a panic that gets through
+// anyway is caught at the boundary and reported by the process's own panic
+// hook.
+
+#![allow(clippy::missing_safety_doc)]
+
+use crate::registry::operands;
+use crate::until::{run_until, Stop, Until};
+use crate::{parse_program, ProgramState};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+pub const INTCODE_ERROR: c_int = -1;
+pub const INTCODE_HALTED: c_int = 0;
+pub const INTCODE_NEED_INPUT: c_int = 1;
+pub const INTCODE_BUDGET: c_int = 2;
+pub const INTCODE_LOOP: c_int = 3;
+
+#[derive(Clone)]
+pub struct IntcodeMachine {
+    state: ProgramState,
+    // Outputs already popped.
+    popped: usize,
+    failed: bool,
+}
+
+// run_until for budget steps, checking each instruction before it runs.
+// None if one would fault.
+fn run_checked(s: &mut ProgramState, budget: u64) -> Option<Stop> {
+    for _ in 0..budget {
+        if s.pc < s.program.len() {
+            operands(s, usize::MAX).ok()?;
+        }
+        match run_until(s, &Until::Steps(1)) {
+            Stop::StepBudget => {}
+            stop => return Some(stop),
+        }
+    }
+    Some(Stop::StepBudget)
+}
+
+fn parse(text: &str) -> Option<Vec<i64>> {
+    let valid = text
+        .split(',')
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .all(|v| v.parse::<i64>().is_ok());
+    if valid {
+        Some(parse_program(text))
+    } else {
+        None
+    }
+}
+
+// Comma separated program text; NULL if it doesn't parse.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_create(text: *const c_char) -> *mut IntcodeMachine {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    let program = match CStr::from_ptr(text).to_str().ok().and_then(parse) {
+        Some(program) => program,
+        None => return ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(IntcodeMachine {
+        state: ProgramState::new(program),
+        popped: 0,
+        failed: false,
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intcode_destroy(m: *mut IntcodeMachine) {
+    if !m.is_null() {
+        drop(Box::from_raw(m));
+    }
+}
+
+// An independent copy of the machine as it is now, outputs included. m
+// must not be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_snapshot(m: *const IntcodeMachine) -> *mut IntcodeMachine {
+    Box::into_raw(Box::new((*m).clone()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intcode_push_input(m: *mut IntcodeMachine, value: i64) {
+    (*m).state.inputs.push(value);
+}
+
+// Runs until the machine halts, needs input it doesn't have, or has run
+// budget instructions. Outputs queue up for intcode_pop_output.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_run(m: *mut IntcodeMachine, budget: u64) -> c_int {
+    let m = &mut *m;
+    if m.failed {
+        return INTCODE_ERROR;
+    }
+    let state = &mut m.state;
+    match catch_unwind(AssertUnwindSafe(|| run_checked(state, budget))) {
+        Ok(Some(Stop::Halted)) => INTCODE_HALTED,
+        Ok(Some(Stop::InputNeeded)) => INTCODE_NEED_INPUT,
+        Ok(Some(Stop::InfiniteLoop { .. })) => INTCODE_LOOP,
+        Ok(Some(_)) => INTCODE_BUDGET,
+        Ok(None) | Err(_) => {
+            m.failed = true;
+            INTCODE_ERROR
+        }
+    }
+}
+
+// Makes intcode_run return INTCODE_LOOP when the machine is caught in a
+// loop it can never leave, instead of using up its budget.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_detect_loops(m: *mut IntcodeMachine) {
+    (*m).state.detect_loops();
+}
+
+// Takes the oldest output not yet popped. Returns 1 if there was one, 0 if
+// not. Popped outputs are dropped once the caller has taken them all.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pop_output(m: *mut IntcodeMachine, value: *mut i64) -> c_int {
+    let m = &mut *m;
+    let v = match m.state.outputs.get(m.popped) {
+        Some(v) => *v,
+        None => return 0,
+    };
+    *value = v;
+    m.popped += 1;
+    if m.popped == m.state.outputs.len() {
+        m.state.clear_outputs();
+        m.popped = 0;
+    }
+    1
+}
+
+// Memory access returns 0, or INTCODE_ERROR for an address out of range.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_read(
+    m: *const IntcodeMachine,
+    addr: u64,
+    value: *mut i64,
+) -> c_int {
+    let s = &(*m).state;
+    if addr >= s.program.len() as u64 {
+        return INTCODE_ERROR;
+    }
+    *value = s.program[addr as usize];
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intcode_write(m: *mut IntcodeMachine, addr: u64, value: i64) -> c_int {
+    let s = &mut (*m).state;
+    if addr >= s.program.len() as u64 {
+        return INTCODE_ERROR;
+    }
+    s.write(addr as usize, value);
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intcode_steps(m: *const IntcodeMachine) -> u64 {
+    (*m).state.steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_run() {
+        unsafe {
+            // Day 5's echo: read a value and write it back out.
+            let text = CString::new("3,0,4,0,99").unwrap();
+            let m = intcode_create(text.as_ptr());
+            assert_eq!(intcode_run(m, 100), INTCODE_NEED_INPUT);
+            let copy = intcode_snapshot(m);
+            intcode_push_input(m, 42);
+            intcode_push_input(copy, 7);
+            assert_eq!(intcode_run(m, 100), INTCODE_HALTED);
+            assert_eq!(intcode_run(copy, 1), INTCODE_BUDGET);
+            assert_eq!(intcode_run(copy, 100), INTCODE_HALTED);
+            let mut v = 0;
+            assert_eq!(intcode_pop_output(m, &mut v), 1);
+            assert_eq!(v, 42);
+            assert_eq!(intcode_pop_output(m, &mut v), 0);
+            assert_eq!(intcode_pop_output(copy, &mut v), 1);
+            assert_eq!(v, 7);
+            assert!((*m).state.outputs.is_empty());
+            assert_eq!(intcode_steps(m), 3);
+
+            assert_eq!(intcode_read(m, 0, &mut v), 0);
+            assert_eq!(v, 42);
+            assert_eq!(intcode_write(m, 0, 99), 0);
+            assert_eq!(intcode_read(m, 0, &mut v), 0);
+            assert_eq!(v, 99);
+            assert_eq!(intcode_read(m, 1 << 40, &mut v), INTCODE_ERROR);
+            intcode_destroy(m);
+            intcode_destroy(copy);
+
+            let bad = CString::new("1,2,x").unwrap();
+            assert!(intcode_create(bad.as_ptr()).is_null());
+            assert!(intcode_create(ptr::null()).is_null());
+
+            // A bad opcode is an error, a tight loop is found when asked.
+            let text = CString::new("42").unwrap();
+            let m = intcode_create(text.as_ptr());
+            assert_eq!(intcode_run(m, 100), INTCODE_ERROR);
+            intcode_destroy(m);
+            let text = CString::new("1105,1,0").unwrap();
+            let m = intcode_create(text.as_ptr());
+            assert_eq!(intcode_run(m, 100), INTCODE_BUDGET);
+            intcode_detect_loops(m);
+            assert_eq!(intcode_run(m, 100), INTCODE_LOOP);
+            intcode_destroy(m);
+
+            // Outputs taken between runs aren't mistaken for a loop that
+            // made no progress.
+            let text = CString::new("104,1,1105,1,0").unwrap();
+            let m = intcode_create(text.as_ptr());
+            intcode_detect_loops(m);
+            for _ in 0..100 {
+                assert_eq!(intcode_run(m, 2), INTCODE_BUDGET);
+                assert_eq!(intcode_pop_output(m, &mut v), 1);
+                assert_eq!(intcode_pop_output(m, &mut v), 0);
+            }
+            assert!((*m).state.outputs.is_empty());
+            intcode_destroy(m);
+
+            // Faults found before the step: an address past memory and an
+            // instruction cut off by the end of memory.
+            let m = intcode_create(text.as_ptr());
+            assert_eq!(intcode_write(m, 0, 4), 0);
+            assert_eq!(intcode_write(m, 1, 1 << 40), 0);
+            assert_eq!(intcode_run(m, 100), INTCODE_ERROR);
+            assert_eq!(intcode_steps(m), 0);
+            intcode_destroy(m);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+// Groups raw outputs into packets and decodes them into typed events.
+// A Protocol says how many outputs make a packet and what they mean;
+// fixed-size arrays are protocols of plain tuples:
+//
+//   let mut tiles = Decoder::<Arcade>::new();
+//   run_program(&mut s);
+//   for event in tiles.events(&s.outputs) {
+//       match event? {
+//           ArcadeEvent::Tile { x, y, tile } => ...,
+//           ArcadeEvent::Score(score) => ...,
+//       }
+//   }
+//
+// A Decoder remembers how far into the outputs it got, so it can be called
+// again as the machine produces more.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::marker::PhantomData;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameError {
+    // Index in the outputs of the packet's first value.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "output {}: {}", self.offset, self.message)
+    }
+}
+
+pub trait Protocol {
+    type Event;
+    // Outputs per packet. Must be at least one; decoding a protocol with
+    // SIZE 0 fails to compile.
+    const SIZE: usize;
+    // Decodes one packet into any number of events.
+    fn decode(values: &[i64], events: &mut Vec<Self::Event>) -> Result<(), String>;
+}
+
+impl<const N: usize> Protocol for [i64; N] {
+    type Event = [i64; N];
+    const SIZE: usize = N;
+
+    fn decode(values: &[i64], events: &mut Vec<[i64; N]>) -> Result<(), String> {
+        let mut packet = [0; N];
+        packet.copy_from_slice(values);
+        events.push(packet);
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ArcadeEvent {
+    Tile { x: i64, y: i64, tile: Tile },
+    Score(i64),
+}
+
+// Day 13: x, y, tile id; x = -1, y = 0 sets the score instead.
+pub struct Arcade;
+
+impl Protocol for Arcade {
+    type Event = ArcadeEvent;
+    const SIZE: usize = 3;
+
+    fn decode(values: &[i64], events: &mut Vec<ArcadeEvent>) -> Result<(), String> {
+        let (x, y, id) = (values[0], values[1], values[2]);
+        if (x, y) == (-1, 0) {
+            events.push(ArcadeEvent::Score(id));
+            return Ok(());
+        }
+        let tile = match id {
+            0 => Tile::Empty,
+            1 => Tile::Wall,
+            2 => Tile::Block,
+            3 => Tile::Paddle,
+            4 => Tile::Ball,
+            _ => return Err(format!("unknown tile {} at {},{}", id, x, y)),
+        };
+        events.push(ArcadeEvent::Tile { x, y, tile });
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Color {
+    Black,
+    White,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Turn {
+    Left,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HullEvent {
+    Paint(Color),
+    Turn(Turn),
+}
+
+// Day 11: the color to paint the current panel, then which way to turn
+// before moving forward one panel.
+pub struct HullPainter;
+
+impl Protocol for HullPainter {
+    type Event = HullEvent;
+    const SIZE: usize = 2;
+
+    fn decode(values: &[i64], events: &mut Vec<HullEvent>) -> Result<(), String> {
+        let color = match values[0] {
+            0 => Color::Black,
+            1 => Color::White,
+            n => return Err(format!("unknown color {}", n)),
+        };
+        let turn = match values[1] {
+            0 => Turn::Left,
+            1 => Turn::Right,
+            n => return Err(format!("unknown turn {}", n)),
+        };
+        events.push(HullEvent::Paint(color));
+        events.push(HullEvent::Turn(turn));
+        Ok(())
+    }
+}
+
+pub struct Decoder<P: Protocol> {
+    consumed: usize,
+    pending: VecDeque<P::Event>,
+    protocol: PhantomData<P>,
+}
+
+impl<P: Protocol> Default for Decoder<P> {
+    fn default() -> Decoder<P> {
+        Decoder {
+            consumed: 0,
+            pending: VecDeque::new(),
+            protocol: PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol> Decoder<P> {
+    // Every packet has to consume output, or Events would never end.
+    const NONEMPTY: () = assert!(P::SIZE > 0, "protocol packets can't be empty");
+
+    pub fn new() -> Decoder<P> {
+        Decoder::default()
+    }
+
+    // Events for the complete packets in outputs not seen before. A host
+    // that clears outputs between calls should call reset as well.
+    pub fn events<'a>(&'a mut self, outputs: &'a [i64]) -> Events<'a, P> {
+        let () = Self::NONEMPTY;
+        Events {
+            decoder: self,
+            outputs,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.consumed = 0;
+        self.pending.clear();
+    }
+
+    // Outputs waiting for the rest of their packet.
+    pub fn partial<'a>(&self, outputs: &'a [i64]) -> &'a [i64] {
+        &outputs[self.consumed.min(outputs.len())..]
+    }
+}
+
+pub struct Events<'a, P: Protocol> {
+    decoder: &'a mut Decoder<P>,
+    outputs: &'a [i64],
+}
+
+impl<'a, P: Protocol> Iterator for Events<'a, P> {
+    type Item = Result<P::Event, FrameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let d = &mut *self.decoder;
+        // A packet may decode to nothing; move on to the next one.
+        loop {
+            if let Some(event) = d.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            let offset = d.consumed;
+            let packet = self.outputs.get(offset..offset + P::SIZE)?;
+            d.consumed += P::SIZE;
+            let mut events = Vec::new();
+            if let Err(message) = P::decode(packet, &mut events) {
+                return Some(Err(FrameError { offset, message }));
+            }
+            d.pending.extend(events);
+        }
+    }
+}
+
+// Events in the complete packets of a finished run's outputs.
+pub fn decode_all<P: Protocol>(outputs: &[i64]) -> Vec<Result<P::Event, FrameError>> {
+    Decoder::<P>::new().events(outputs).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, run_program, ProgramState, ReturnState};
+
+    #[test]
+    fn test_run() {
+        // Breakout's first screen, fed to the decoder as it is drawn.
+        let mut s = ProgramState::new(parse_program(include_str!("../../13/input.txt")));
+        let mut decoder = Decoder::<Arcade>::new();
+        let mut blocks = 0;
+        let mut score = None;
+        while s.return_state == ReturnState::ProducedOutput {
+            run_program(&mut s);
+            for event in decoder.events(&s.outputs) {
+                match event.unwrap() {
+                    ArcadeEvent::Tile {
+                        tile: Tile::Block, ..
+                    } => blocks += 1,
+                    ArcadeEvent::Score(n) => score = Some(n),
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(score, Some(0));
+        assert_eq!(blocks, s.outputs.chunks(3).filter(|t| t[2] == 2).count());
+        assert!(decoder.partial(&s.outputs).is_empty());
+
+        let triples = decode_all::<[i64; 3]>(&s.outputs);
+        assert_eq!(triples.len() * 3, s.outputs.len());
+        assert_eq!(triples[0], Ok([s.outputs[0], s.outputs[1], s.outputs[2]]));
+
+        // Outputs trickle in one at a time; each pair becomes a Paint and a
+        // Turn once both halves are there.
+        let outputs = [1, 0, 0, 1, 1];
+        let mut decoder = Decoder::<HullPainter>::new();
+        let mut events = Vec::new();
+        for i in 1..=outputs.len() {
+            events.extend(decoder.events(&outputs[..i]).map(|e| e.unwrap()));
+        }
+        assert_eq!(
+            events,
+            [
+                HullEvent::Paint(Color::White),
+                HullEvent::Turn(Turn::Left),
+                HullEvent::Paint(Color::Black),
+                HullEvent::Turn(Turn::Right),
+            ]
+        );
+        assert_eq!(decoder.partial(&outputs), [1]);
+
+        // A million packets that decode to nothing, on a small stack.
+        struct Silent;
+        impl Protocol for Silent {
+            type Event = ();
+            const SIZE: usize = 1;
+            fn decode(_: &[i64], _: &mut Vec<()>) -> Result<(), String> {
+                Ok(())
+            }
+        }
+        let quiet = vec![0; 1000000];
+        let decoded = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(move || decode_all::<Silent>(&quiet).len())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(decoded, 0);
+
+        let bad = decode_all::<HullPainter>(&[0, 1, 2, 0]);
+        assert_eq!(bad.len(), 3);
+        assert_eq!(
+            bad[2].as_ref().unwrap_err().to_string(),
+            "output 2: unknown color 2"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+// Memory access counts per address, bucketed into windows of steps, and
+// rendered as images: a timeline with addresses left to right and one row
+// per window, and a strip of the totals. Red is writes, green reads and
+// blue instruction fetches; brightness is log-scaled to the busiest pixel.
+//
+// Images are binary PPM (P6) or, in gray, PGM (P5), which most viewers
+// open and anything can convert.
+
+use crate::disasm::{decode, param_count, write_param};
+use crate::{step_program, Mode, ProgramState};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Counts {
+    pub reads: u64,
+    pub writes: u64,
+    pub fetches: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.reads += other.reads;
+        self.writes += other.writes;
+        self.fetches += other.fetches;
+    }
+
+    fn channels(&self) -> [u64; 3] {
+        [self.writes, self.reads, self.fetches]
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    // Row by row, top to bottom.
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut res = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for p in &self.pixels {
+            res.extend_from_slice(p);
+        }
+        res
+    }
+
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut res = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for p in &self.pixels {
+            res.push(*p.iter().max().unwrap());
+        }
+        res
+    }
+
+    // PGM for a .pgm path, PPM otherwise.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let bytes = match path.extension().and_then(|e| e.to_str()) {
+            Some("pgm") => self.to_pgm(),
+            _ => self.to_ppm(),
+        };
+        fs::write(path, bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeatMap {
+    // Steps per window.
+    pub window: u64,
+    pub windows: Vec<HashMap<usize, Counts>>,
+    pub totals: BTreeMap<usize, Counts>,
+}
+
+impl HeatMap {
+    pub fn new(window: u64) -> HeatMap {
+        assert!(window > 0, "window must be at least one step");
+        HeatMap {
+            window,
+            windows: Vec::new(),
+            totals: BTreeMap::new(),
+        }
+    }
+
+    fn count(&mut self, step: u64, addr: usize, counts: Counts) {
+        let w = (step / self.window) as usize;
+        if self.windows.len() <= w {
+            self.windows.resize(w + 1, HashMap::new());
+        }
+        self.windows[w].entry(addr).or_default().add(&counts);
+        self.totals.entry(addr).or_default().add(&counts);
+    }
+
+    // Drop-in replacement for run_program that counts the accesses of each
+    // instruction it executes.
+    pub fn run_program(&mut self, s: &mut ProgramState) {
+        while s.pc < s.program.len() {
+            let pc = s.pc;
+            let steps = s.steps;
+            let mut accesses = Vec::new();
+            let mut fetched = pc..pc;
+            if let Some((op, modes)) = decode(s.program[pc]) {
+                let count = param_count(op);
+                for (i, mode) in modes.iter().enumerate().take(count) {
+                    let val = s.program[pc + 1 + i];
+                    let addr = match mode {
+                        Mode::Position => val,
+                        Mode::Immediate => continue,
+                        Mode::Relative => s.relative_base + val,
+                    };
+                    accesses.push((addr as usize, write_param(op) == Some(i)));
+                }
+                fetched = pc..pc + 1 + count;
+            }
+
+            let yielded = step_program(s);
+
+            if s.steps == steps {
+                // Stalled on input; nothing ran.
+                return;
+            }
+            for addr in fetched {
+                let counts = Counts {
+                    fetches: 1,
+                    ..Counts::default()
+                };
+                self.count(steps, addr, counts);
+            }
+            for (addr, write) in accesses {
+                let counts = if write {
+                    Counts {
+                        writes: 1,
+                        ..Counts::default()
+                    }
+                } else {
+                    Counts {
+                        reads: 1,
+                        ..Counts::default()
+                    }
+                };
+                self.count(steps, addr, counts);
+            }
+
+            if yielded {
+                return;
+            }
+        }
+    }
+
+    // The lowest and one past the highest address touched.
+    pub fn extent(&self) -> (usize, usize) {
+        match (self.totals.keys().next(), self.totals.keys().next_back()) {
+            (Some(lo), Some(hi)) => (*lo, hi + 1),
+            _ => (0, 0),
+        }
+    }
+
+    // Columns each cover an equal share of start..end, and there are never
+    // more columns than addresses.
+    fn column(start: usize, end: usize, columns: usize, addr: usize) -> Option<usize> {
+        if addr < start || addr >= end {
+            return None;
+        }
+        Some((addr - start) * columns / (end - start))
+    }
+
+    fn render(rows: &[Vec<Counts>], columns: usize) -> Image {
+        let mut max = [0; 3];
+        for c in rows.iter().flatten() {
+            for (m, v) in max.iter_mut().zip(&c.channels()) {
+                *m = (*m).max(*v);
+            }
+        }
+        let scale = |v: u64, max: u64| {
+            if v == 0 {
+                0
+            } else {
+                // Anything touched at all stays visible.
+                (32.0 + 223.0 * (v as f64).ln_1p() / (max as f64).ln_1p()) as u8
+            }
+        };
+
+        let pixels = rows
+            .iter()
+            .flatten()
+            .map(|c| {
+                let ch = c.channels();
+                [
+                    scale(ch[0], max[0]),
+                    scale(ch[1], max[1]),
+                    scale(ch[2], max[2]),
+                ]
+            })
+            .collect();
+        Image {
+            width: columns,
+            height: rows.len(),
+            pixels,
+        }
+    }
+
+    // One row per window, for addresses start..end.
+    pub fn timeline(&self, start: usize, end: usize, columns: usize) -> Image {
+        let columns = columns.min(end.saturating_sub(start));
+        let rows: Vec<Vec<Counts>> = self
+            .windows
+            .iter()
+            .map(|w| {
+                let mut row = vec![Counts::default(); columns];
+                for (addr, c) in w {
+                    if let Some(x) = HeatMap::column(start, end, columns, *addr) {
+                        row[x].add(c);
+                    }
+                }
+                row
+            })
+            .collect();
+        HeatMap::render(&rows, columns)
+    }
+
+    // Totals over the whole run, repeated height times.
+    pub fn strip(&self, start: usize, end: usize, columns: usize, height: usize) -> Image {
+        let columns = columns.min(end.saturating_sub(start));
+        let mut row = vec![Counts::default(); columns];
+        for (addr, c) in self.totals.range(start..end.max(start)) {
+            if let Some(x) = HeatMap::column(start, end, columns, *addr) {
+                row[x].add(c);
+            }
+        }
+        HeatMap::render(&vec![row; height], columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, ReturnState};
+
+    #[test]
+    fn test_run() {
+        let mut s = ProgramState::new(parse_program(include_str!("../../09/input")));
+        s.inputs.push(1);
+        let mut heat = HeatMap::new(100);
+        while s.return_state == ReturnState::ProducedOutput {
+            heat.run_program(&mut s);
+        }
+        assert_eq!(s.return_state, ReturnState::Break);
+        assert_eq!(heat.windows.len() as u64, (s.steps - 1) / 100 + 1);
+
+        // The first instruction, 1102,34463338,34463338,63, runs once and
+        // writes 63, which the self-test keeps rewriting.
+        assert_eq!(heat.totals[&0].fetches, 1);
+        assert!(heat.totals[&63].writes > 1);
+        let fetched: u64 = heat.totals.values().map(|c| c.fetches).sum();
+        assert!(fetched > s.steps * 2);
+
+        let (start, end) = heat.extent();
+        assert_eq!(start, 0);
+        assert!(end > 973);
+        let image = heat.timeline(start, end, 256);
+        assert_eq!((image.width, image.height), (256, heat.windows.len()));
+        let ppm = image.to_ppm();
+        let header = format!("P6\n256 {}\n255\n", image.height);
+        assert!(ppm.starts_with(header.as_bytes()));
+        assert_eq!(ppm.len(), header.len() + 256 * image.height * 3);
+        assert!(image.pixels[0][2] > 0);
+
+        // Columns never outnumber addresses.
+        let strip = heat.strip(60, 70, 100, 4);
+        assert_eq!((strip.width, strip.height), (10, 4));
+        assert_eq!(strip.pixels[3], strip.pixels[13]);
+        assert_eq!(strip.to_pgm().len(), "P5\n10 4\n255\n".len() + 40);
+
+        // Waiting for input touches nothing.
+        let mut s = ProgramState::new(parse_program("3,0,99"));
+        let mut heat = HeatMap::new(10);
+        heat.run_program(&mut s);
+        assert_eq!(s.return_state, ReturnState::NeedMoreInput);
+        assert!(heat.totals.is_empty());
+    }
+}
@@ -0,0 +1,373 @@
+pub mod asm;
+pub mod batch;
+pub mod callstack;
+pub mod cast;
+pub mod catalog;
+pub mod compile;
+pub mod coverage;
+pub mod debug;
+pub mod diff;
+pub mod disasm;
+pub mod ffi;
+pub mod framing;
+pub mod heat;
+pub mod lint;
+pub mod load;
+pub mod lockstep;
+pub mod loops;
+pub mod memory;
+pub mod network;
+pub mod quota;
+pub mod registry;
+pub mod scan;
+pub mod scheduler;
+pub mod seed;
+pub mod session;
+pub mod symbolic;
+pub mod until;
+
+use loops::LoopDetector;
+use memory::Memory;
+use registry::Registry;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+pub fn digit_to_mode(d: i64) -> Mode {
+    match d {
+        0 => Mode::Position,
+        1 => Mode::Immediate,
+        2 => Mode::Relative,
+        _ => {
+            panic!("unexpected mode digit: {}", d);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Opcode {
+    Addition,
+    Multiplication,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelativeBase,
+    Break,
+}
+
+pub fn digit_to_opcode(d: i64) -> Opcode {
+    match d {
+        1 => Opcode::Addition,
+        2 => Opcode::Multiplication,
+        3 => Opcode::Input,
+        4 => Opcode::Output,
+        5 => Opcode::JumpIfTrue,
+        6 => Opcode::JumpIfFalse,
+        7 => Opcode::LessThan,
+        8 => Opcode::Equals,
+        9 => Opcode::AdjustRelativeBase,
+        99 => Opcode::Break,
+        _ => {
+            panic!("Illegal opcode {:?}", d);
+        }
+    }
+}
+
+// Returns (op, mode3, mode2, mode1), same order as the digits are written.
+pub fn parse_op(n: i64) -> (Opcode, Mode, Mode, Mode) {
+    (
+        digit_to_opcode(n % 100),
+        digit_to_mode(n / 10000 % 10),
+        digit_to_mode(n / 1000 % 10),
+        digit_to_mode(n / 100 % 10),
+    )
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReturnState {
+    NeedMoreInput,
+    ProducedOutput,
+    Break,
+    // Only reported with loop detection on. start and end are the lowest and
+    // highest instruction address in the loop.
+    InfiniteLoop { start: usize, end: usize },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ProgramState {
+    pub program: Memory,
+    pub return_state: ReturnState,
+    pub inputs: Vec<i64>,
+    pub outputs: Vec<i64>,
+    pub pc: usize,
+    pub input_counter: usize,
+    pub relative_base: i64,
+    pub steps: u64,
+    // Step at which each consumed input was read, parallel to inputs.
+    pub input_steps: Vec<u64>,
+    pub loop_detector: Option<LoopDetector>,
+    // Opcode table to run on instead of the built-in instructions.
+    pub registry: Option<Arc<Registry>>,
+}
+
+impl ProgramState {
+    pub fn new(program: Vec<i64>) -> ProgramState {
+        ProgramState::from_image(Arc::new(program))
+    }
+
+    // Starts a machine on a shared image; memory is only copied a page at a
+    // time as the machine writes to it.
+    pub fn from_image(image: Arc<Vec<i64>>) -> ProgramState {
+        ProgramState {
+            program: Memory::new(image),
+            return_state: ReturnState::ProducedOutput,
+            inputs: vec![],
+            outputs: vec![],
+            pc: 0,
+            input_counter: 0,
+            relative_base: 0,
+            steps: 0,
+            input_steps: vec![],
+            loop_detector: None,
+            registry: None,
+        }
+    }
+
+    // Save-state for search and backtracking. Memory is shared, not copied:
+    // a write copies only the page it touches and the part of the page
+    // table above it (see Memory).
+    pub fn fork(&self) -> ProgramState {
+        self.clone()
+    }
+
+    // Makes the machine stop with InfiniteLoop instead of spinning forever.
+    // Memory changes made from outside should go through write, or the
+    // detector will not see them.
+    pub fn detect_loops(&mut self) {
+        self.loop_detector = Some(LoopDetector::new());
+    }
+
+    // Drops the outputs produced so far. The loop detector tells progress
+    // by the number of outputs, so it starts over.
+    pub fn clear_outputs(&mut self) {
+        self.outputs.clear();
+        if let Some(detector) = &mut self.loop_detector {
+            detector.restart();
+        }
+    }
+
+    pub fn write(&mut self, addr: usize, value: i64) {
+        if let Some(detector) = &mut self.loop_detector {
+            detector.write(addr, self.program[addr], value);
+        }
+        self.program[addr] = value;
+    }
+}
+
+fn read_param(s: &ProgramState, mode: Mode, val: i64) -> i64 {
+    match mode {
+        Mode::Position => s.program[val as usize],
+        Mode::Immediate => val,
+        Mode::Relative => s.program[(s.relative_base + val) as usize],
+    }
+}
+
+fn write_index(s: &ProgramState, mode: Mode, val: i64) -> usize {
+    match mode {
+        Mode::Position => val as usize,
+        Mode::Immediate => panic!("illegal immediate mode"),
+        Mode::Relative => (s.relative_base + val) as usize,
+    }
+}
+
+// Executes a single instruction. Returns true when the machine yielded, in
+// which case s.return_state says why.
+pub fn step_program(s: &mut ProgramState) -> bool {
+    if let Some(registry) = s.registry.clone() {
+        return registry.step(s);
+    }
+    let (op, mode3, mode2, mode1) = parse_op(s.program[s.pc]);
+
+    // Only the operands the instruction has, so that one at the very end
+    // of memory doesn't read past it.
+    let mut vals = [0; 3];
+    for (i, val) in vals.iter_mut().enumerate().take(disasm::param_count(op)) {
+        *val = s.program[s.pc + 1 + i];
+    }
+    let [val1, val2, val3] = vals;
+    let pc = s.pc;
+
+    match op {
+        Opcode::Addition => {
+            let c_index = write_index(s, mode3, val3);
+            s.write(
+                c_index,
+                read_param(s, mode1, val1) + read_param(s, mode2, val2),
+            );
+            s.pc += 4;
+        }
+        Opcode::Multiplication => {
+            let c_index = write_index(s, mode3, val3);
+            s.write(
+                c_index,
+                read_param(s, mode1, val1) * read_param(s, mode2, val2),
+            );
+            s.pc += 4;
+        }
+        Opcode::Input => {
+            if s.inputs.len() <= s.input_counter {
+                s.return_state = ReturnState::NeedMoreInput;
+                return true;
+            }
+
+            let a_index = write_index(s, mode1, val1);
+            s.write(a_index, s.inputs[s.input_counter]);
+            s.input_counter += 1;
+            s.input_steps.push(s.steps);
+            s.pc += 2;
+        }
+        Opcode::Output => {
+            let a = read_param(s, mode1, val1);
+            s.outputs.push(a);
+            s.pc += 2;
+            s.steps += 1;
+
+            s.return_state = ReturnState::ProducedOutput;
+            return true;
+        }
+        Opcode::JumpIfTrue => {
+            if read_param(s, mode1, val1) != 0 {
+                s.pc = read_param(s, mode2, val2) as usize;
+            } else {
+                s.pc += 3;
+            }
+        }
+        Opcode::JumpIfFalse => {
+            if read_param(s, mode1, val1) == 0 {
+                s.pc = read_param(s, mode2, val2) as usize;
+            } else {
+                s.pc += 3;
+            }
+        }
+        Opcode::LessThan => {
+            let c_index = write_index(s, mode3, val3);
+            s.write(
+                c_index,
+                (read_param(s, mode1, val1) < read_param(s, mode2, val2)) as i64,
+            );
+            s.pc += 4;
+        }
+        Opcode::Equals => {
+            let c_index = write_index(s, mode3, val3);
+            s.write(
+                c_index,
+                (read_param(s, mode1, val1) == read_param(s, mode2, val2)) as i64,
+            );
+            s.pc += 4;
+        }
+        Opcode::AdjustRelativeBase => {
+            s.relative_base += read_param(s, mode1, val1);
+            s.pc += 2;
+        }
+        Opcode::Break => {
+            s.steps += 1;
+            s.return_state = ReturnState::Break;
+            return true;
+        }
+    }
+
+    s.steps += 1;
+    check_loop(s, pc)
+}
+
+// Runs the loop detector, if any, after the instruction at pc. Returns true
+// when it found a loop.
+fn check_loop(s: &mut ProgramState, pc: usize) -> bool {
+    if let Some(detector) = &mut s.loop_detector {
+        detector.executed(pc);
+        if s.pc <= pc {
+            let io = (s.input_counter, s.outputs.len());
+            if let Some((start, end)) =
+                detector.backward_jump(&s.program, s.pc, s.relative_base, io)
+            {
+                s.return_state = ReturnState::InfiniteLoop { start, end };
+                return true;
+            }
+        }
+    }
+    false
+}
+
+pub fn run_program(s: &mut ProgramState) {
+    while s.pc < s.program.len() {
+        if step_program(s) {
+            return;
+        }
+    }
+}
+
+pub fn parse_program(input: &str) -> Vec<i64> {
+    let mut program: Vec<i64> = input
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<i64>().unwrap())
+        .collect();
+
+    program.resize(1000000, 0);
+
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_to_end(input: &str, inputs: Vec<i64>) -> ProgramState {
+        let mut program = ProgramState::new(parse_program(input));
+        program.inputs = inputs;
+
+        while program.return_state == ReturnState::ProducedOutput {
+            run_program(&mut program);
+        }
+
+        program
+    }
+
+    #[test]
+    fn test_run() {
+        assert_eq!(
+            run_to_end("1,0,0,0,99", vec![]).program.range(0, 5),
+            [2, 0, 0, 0, 99]
+        );
+        assert_eq!(
+            run_to_end("2,4,4,5,99,0", vec![]).program.range(0, 6),
+            [2, 4, 4, 5, 99, 9801]
+        );
+        assert_eq!(run_to_end("3,9,8,9,10,9,4,9,99,-1,8", vec![8]).outputs, [1]);
+
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(
+            run_to_end(quine, vec![]).outputs,
+            parse_program(quine)[0..16].to_vec()
+        );
+
+        assert_eq!(
+            run_to_end("1102,34915192,34915192,7,4,7,99,0", vec![]).outputs,
+            [1219070632396864]
+        );
+
+        let waiting = run_to_end("3,0,4,0,99", vec![]);
+        assert_eq!(waiting.return_state, ReturnState::NeedMoreInput);
+        assert_eq!(waiting.pc, 0);
+        assert_eq!(waiting.steps, 0);
+    }
+}
@@ -0,0 +1,259 @@
+// Static checks for problems run_program would only find by panicking.
+// Walks the code reachable from address 0, following fallthrough and jumps
+// to constant addresses. Jumps through memory can't be followed, except for
+// the usual call sequence: a return address pushed onto the relative base
+// stack as an immediate, then an unconditional jump to a constant address.
+// The instruction after such a jump is walked once its address has been
+// pushed somewhere in reachable code.
+
+use crate::disasm::trimmed_len;
+use crate::registry::{Flow, Param, Registry};
+use crate::Mode;
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Issue {
+    UnknownOpcode(i64),
+    ImmediateWrite(usize),
+    RunsPastEnd,
+    JumpOutsideImage(i64),
+    // A constant write into the cells of reachable code.
+    SelfModifying(usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Finding {
+    pub addr: usize,
+    pub severity: Severity,
+    pub issue: Issue,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{} at {}: ", severity, self.addr)?;
+        match &self.issue {
+            Issue::UnknownOpcode(n) => write!(f, "unknown opcode {}", n),
+            Issue::ImmediateWrite(i) => write!(f, "operand {} writes in immediate mode", i + 1),
+            Issue::RunsPastEnd => write!(f, "runs past the end of the program"),
+            Issue::JumpOutsideImage(target) => {
+                write!(f, "jumps to {}, outside the program", target)
+            }
+            Issue::SelfModifying(target) => write!(f, "writes to code at {}", target),
+        }
+    }
+}
+
+pub fn lint(program: &[i64]) -> Vec<Finding> {
+    lint_with(&Registry::builtin(), program)
+}
+
+// Findings sorted by address. Zero padding at the end of program does not
+// count as part of it, except as operands of the last instruction.
+pub fn lint_with(registry: &Registry, program: &[i64]) -> Vec<Finding> {
+    let len = trimmed_len(program);
+    let mut res = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut code_cells = BTreeSet::new();
+    let mut pushed = BTreeSet::new();
+    let mut const_writes = Vec::new();
+    let mut after_jumps = BTreeSet::new();
+    let mut work = vec![0];
+
+    let mut finding = |addr, severity, issue| {
+        res.push(Finding {
+            addr,
+            severity,
+            issue,
+        })
+    };
+
+    loop {
+        let addr = match work.pop() {
+            Some(addr) => addr,
+            None => {
+                // Nothing left but the instructions after calls whose return
+                // address was pushed somewhere.
+                let returns: Vec<usize> = after_jumps
+                    .iter()
+                    .filter(|a| pushed.contains(&(**a as i64)))
+                    .cloned()
+                    .collect();
+                if returns.is_empty() {
+                    break;
+                }
+                for a in &returns {
+                    after_jumps.remove(a);
+                }
+                work = returns;
+                continue;
+            }
+        };
+        if !visited.insert(addr) {
+            continue;
+        }
+        if addr >= len {
+            finding(addr, Severity::Error, Issue::RunsPastEnd);
+            continue;
+        }
+
+        let n = program[addr];
+        let (def, modes) = match registry.decode(n) {
+            Some(d) => d,
+            None => {
+                finding(addr, Severity::Error, Issue::UnknownOpcode(n));
+                continue;
+            }
+        };
+        let next = addr + 1 + def.params.len();
+        if next > program.len() {
+            finding(addr, Severity::Error, Issue::RunsPastEnd);
+            continue;
+        }
+        code_cells.extend(addr..next);
+        let operands = &program[addr + 1..next];
+
+        let pushes = def
+            .params
+            .iter()
+            .zip(&modes)
+            .any(|(p, m)| *p == Param::Write && *m == Mode::Relative);
+        for (i, param) in def.params.iter().enumerate() {
+            if modes[i] == Mode::Immediate {
+                if pushes {
+                    pushed.insert(operands[i]);
+                }
+                if *param == Param::Write {
+                    finding(addr, Severity::Error, Issue::ImmediateWrite(i));
+                }
+            }
+            if *param == Param::Write && modes[i] == Mode::Position {
+                const_writes.push((addr, operands[i]));
+            }
+        }
+
+        match def.flow {
+            Flow::Halt => {}
+            Flow::Branch {
+                cond,
+                target,
+                if_nonzero,
+            } => {
+                let target = match modes[target] {
+                    Mode::Immediate => Some(operands[target]),
+                    _ => None,
+                };
+                if let Some(target) = target {
+                    if target < 0 || target as usize >= len {
+                        finding(addr, Severity::Error, Issue::JumpOutsideImage(target));
+                    } else {
+                        work.push(target as usize);
+                    }
+                }
+                let always = modes[cond] == Mode::Immediate && (operands[cond] != 0) == if_nonzero;
+                if !always {
+                    work.push(next);
+                } else if target.is_some() {
+                    after_jumps.insert(next);
+                }
+            }
+            // Wherever it goes can't be followed, but it may come back.
+            Flow::Unknown => {
+                after_jumps.insert(next);
+            }
+            Flow::Next => work.push(next),
+        }
+    }
+
+    for (addr, target) in const_writes {
+        if target >= 0 && code_cells.contains(&(target as usize)) {
+            finding(
+                addr,
+                Severity::Warning,
+                Issue::SelfModifying(target as usize),
+            );
+        }
+    }
+
+    res.sort_by_key(|f| f.addr);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_run() {
+        for input in &[
+            include_str!("../../09/input"),
+            include_str!("../../13/input.txt"),
+            include_str!("../../15/input"),
+            include_str!("../../19/input.txt"),
+            include_str!("../../21/input.txt"),
+        ] {
+            let findings = lint(&parse_program(input));
+            assert!(
+                findings.iter().all(|f| f.severity == Severity::Warning),
+                "{:?}",
+                findings
+            );
+        }
+
+        let findings = lint(&parse_program(
+            "1101,1,2,0,1105,1,12,1,1,2,3,4,1105,1,50,55,1,2",
+        ));
+        let text: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "warning at 0: writes to code at 0",
+                "error at 12: jumps to 50, outside the program",
+            ]
+        );
+
+        // Falls into an unknown opcode after a conditional jump, writes to
+        // an immediate and then runs off the end.
+        let findings = lint(&[1005, 7, 4, 77, 11101, 1, 2, 3, 4]);
+        assert_eq!(
+            findings.iter().map(|f| &f.issue).collect::<Vec<_>>(),
+            [
+                &Issue::UnknownOpcode(77),
+                &Issue::ImmediateWrite(2),
+                &Issue::RunsPastEnd
+            ]
+        );
+
+        // Registered instructions are followed by their Flow: a jump with
+        // the target first, and a halt with data after it.
+        use crate::registry::{Effect, OpcodeDef};
+        let mut r = Registry::builtin();
+        let jnz = OpcodeDef::new(50, "jnz", &[Param::Read, Param::Read], |a| {
+            if a.read(1) != 0 {
+                Effect::Jump(a.read(0) as usize)
+            } else {
+                Effect::Next
+            }
+        });
+        r.register(jnz.with_flow(Flow::Branch {
+            cond: 1,
+            target: 0,
+            if_nonzero: true,
+        }))
+        .unwrap();
+        r.register(OpcodeDef::new(98, "stop", &[], |_| Effect::Halt).with_flow(Flow::Halt))
+            .unwrap();
+        assert_eq!(lint_with(&r, &[1150, 4, 1, 77, 98, 12345]), []);
+    }
+}
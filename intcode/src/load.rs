@@ -0,0 +1,244 @@
+// Reads and writes programs in any of three formats:
+//
+//   comma       1,2,3 as in the puzzle inputs; line breaks count as commas
+//   whitespace  values separated by spaces or newlines
+//   binary      "ICB1" followed by each value as 8 bytes, little-endian
+//
+// The format is picked from the file extension (.icb for binary) or else
+// from the content. A path of "-" means stdin or stdout.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"ICB1";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Comma,
+    Whitespace,
+    Binary,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    BadToken {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    BadBinary(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::BadToken {
+                line,
+                column,
+                token,
+            } => write!(f, "{}:{}: bad value {:?}", line, column, token),
+            LoadError::BadBinary(msg) => write!(f, "bad binary program: {}", msg),
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "comma" => Some(Format::Comma),
+            "whitespace" | "lines" => Some(Format::Whitespace),
+            "binary" => Some(Format::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Format> {
+        match path.as_ref().extension()?.to_str()? {
+            "icb" => Some(Format::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn detect(bytes: &[u8]) -> Format {
+        if bytes.starts_with(MAGIC) {
+            Format::Binary
+        } else if bytes.contains(&b',') {
+            Format::Comma
+        } else {
+            Format::Whitespace
+        }
+    }
+}
+
+// Positions are 1-based, in characters.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn parse_tokens<'a, I: Iterator<Item = &'a str>>(
+    text: &'a str,
+    tokens: I,
+) -> Result<Vec<i64>, LoadError> {
+    let mut res = Vec::new();
+    for token in tokens {
+        let trimmed = token.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        match trimmed.parse::<i64>() {
+            Ok(v) => res.push(v),
+            Err(_) => {
+                let offset = trimmed.as_ptr() as usize - text.as_ptr() as usize;
+                let (line, column) = position(text, offset);
+                return Err(LoadError::BadToken {
+                    line,
+                    column,
+                    token: trimmed.to_owned(),
+                });
+            }
+        }
+    }
+    Ok(res)
+}
+
+pub fn parse_text(text: &str, format: Format) -> Result<Vec<i64>, LoadError> {
+    match format {
+        Format::Comma => parse_tokens(text, text.split([',', '\n'])),
+        Format::Whitespace => parse_tokens(text, text.split_whitespace()),
+        Format::Binary => Err(LoadError::BadBinary("not a text format".to_owned())),
+    }
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Vec<i64>, LoadError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(LoadError::BadBinary("missing ICB1 header".to_owned()));
+    }
+
+    let values = &bytes[MAGIC.len()..];
+    if !values.len().is_multiple_of(8) {
+        return Err(LoadError::BadBinary("truncated last value".to_owned()));
+    }
+    Ok(values
+        .chunks(8)
+        .map(|b| i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+        .collect())
+}
+
+// Parses bytes in the given format, or the detected one.
+pub fn parse(bytes: &[u8], format: Option<Format>) -> Result<Vec<i64>, LoadError> {
+    match format.unwrap_or_else(|| Format::detect(bytes)) {
+        Format::Binary => parse_binary(bytes),
+        format => {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|e| LoadError::BadBinary(format!("not text or binary: {}", e)))?;
+            parse_text(text, format)
+        }
+    }
+}
+
+// The program as stored, without padding.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<i64>, LoadError> {
+    let path = path.as_ref();
+    let mut bytes = Vec::new();
+    if path == Path::new("-") {
+        io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        bytes = fs::read(path)?;
+    }
+    parse(&bytes, Format::from_extension(path))
+}
+
+// Like parse_program on the file's contents: padded with zeroes to the
+// usual 1,000,000 cells.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<i64>, LoadError> {
+    let mut program = read(path)?;
+    program.resize(program.len().max(1000000), 0);
+    Ok(program)
+}
+
+pub fn to_bytes(program: &[i64], format: Format) -> Vec<u8> {
+    match format {
+        Format::Comma => {
+            let values: Vec<String> = program.iter().map(|v| v.to_string()).collect();
+            format!("{}\n", values.join(",")).into_bytes()
+        }
+        Format::Whitespace => program
+            .iter()
+            .map(|v| format!("{}\n", v))
+            .collect::<String>()
+            .into_bytes(),
+        Format::Binary => {
+            let mut res = MAGIC.to_vec();
+            for v in program {
+                res.extend_from_slice(&v.to_le_bytes());
+            }
+            res
+        }
+    }
+}
+
+pub fn save<P: AsRef<Path>>(path: P, program: &[i64], format: Format) -> io::Result<()> {
+    let bytes = to_bytes(program, format);
+    if path.as_ref() == Path::new("-") {
+        io::stdout().write_all(&bytes)
+    } else {
+        fs::write(path, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let day9 = include_str!("../../09/input");
+        let program = parse(day9.as_bytes(), None).unwrap();
+        assert_eq!(program.len(), 973);
+        assert_eq!(program[0], 1102);
+
+        for format in &[Format::Comma, Format::Whitespace, Format::Binary] {
+            let bytes = to_bytes(&program, *format);
+            assert_eq!(Format::detect(&bytes), *format);
+            assert_eq!(parse(&bytes, None).unwrap(), program);
+        }
+        let binary = to_bytes(&[0, -1, 1, i64::MAX, i64::MIN, 300], Format::Binary);
+        assert_eq!(binary.len(), 4 + 6 * 8);
+        assert_eq!(binary[12..20], [0xff; 8]);
+        assert_eq!(binary[44..52], [44, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            parse_binary(&binary).unwrap(),
+            [0, -1, 1, i64::MAX, i64::MIN, 300]
+        );
+        assert!(parse_binary(&binary[..binary.len() - 1]).is_err());
+
+        assert_eq!(parse(b"1 2\n-3\t4\n", None).unwrap(), [1, 2, -3, 4]);
+        assert_eq!(parse(b"1,2,\n3,\n", None).unwrap(), [1, 2, 3]);
+
+        match parse("1,2,3,\n4,5x,6".as_bytes(), None) {
+            Err(LoadError::BadToken {
+                line,
+                column,
+                token,
+            }) => assert_eq!((line, column, token.as_str()), (2, 3, "5x")),
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse(b"1 2 3,4", Some(Format::Whitespace)) {
+            Err(e) => assert_eq!(e.to_string(), "1:5: bad value \"3,4\""),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
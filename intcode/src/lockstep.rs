@@ -0,0 +1,347 @@
+// Runs two machines side by side, one instruction at a time, and stops at
+// the first instruction after which they differ: in where they jump, what
+// they write to memory or what they output. Meant for two runs of the same
+// program on different inputs, like 05's diagnostic with 1 and with 5, or
+// two springscripts for 21.
+//
+// Reading different inputs is the expected difference, so input writes are
+// not compared unless asked for; the divergence is where they first matter.
+
+use crate::disasm::{decode, decode_at, param_count, write_param, Instruction};
+use crate::{step_program, Mode, Opcode, ProgramState, ReturnState};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+
+// Executed instructions kept for the report.
+const TRACE_LEN: usize = 8;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Difference {
+    // Where each machine went next.
+    Pc(usize, usize),
+    // (address, value) written by each, if anything.
+    Write(Option<(usize, i64)>, Option<(usize, i64)>),
+    Output(i64, i64),
+    // One machine stopped and the other did not.
+    Stopped(ReturnState, ReturnState),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Divergence {
+    // Instructions both machines executed before the one that differed.
+    pub step: u64,
+    // The instruction that differed, at the same address in both.
+    pub pc: usize,
+    pub difference: Difference,
+    // Addresses behind the difference: those written, and those read whose
+    // values differ between the machines.
+    pub addresses: Vec<usize>,
+}
+
+pub struct Lockstep {
+    pub a: ProgramState,
+    pub b: ProgramState,
+    pub compare_writes: bool,
+    pub compare_inputs: bool,
+    pub max_steps: u64,
+    pub steps: u64,
+    trace: VecDeque<usize>,
+    history: [HashMap<usize, Vec<(u64, i64)>>; 2],
+}
+
+// The addresses the instruction at s.pc reads, and the one it writes.
+fn accesses(s: &ProgramState) -> (Vec<usize>, Option<usize>) {
+    let mut reads = Vec::new();
+    let mut write = None;
+    if let Some((op, modes)) = decode(s.program[s.pc]) {
+        for (i, mode) in modes.iter().enumerate().take(param_count(op)) {
+            let val = s.program[s.pc + 1 + i];
+            let addr = match mode {
+                Mode::Position => val,
+                Mode::Immediate => continue,
+                Mode::Relative => s.relative_base + val,
+            } as usize;
+            if write_param(op) == Some(i) {
+                write = Some(addr);
+            } else {
+                reads.push(addr);
+            }
+        }
+    }
+    (reads, write)
+}
+
+// Ended: halted, waiting for input or stuck in a loop.
+fn stopped(s: &ProgramState, yielded: bool) -> bool {
+    yielded && s.return_state != ReturnState::ProducedOutput
+}
+
+fn instruction(s: &ProgramState, addr: usize) -> Instruction {
+    let end = (addr + 4).min(s.program.len());
+    let mut ins = decode_at(&s.program.range(addr, end), 0);
+    ins.addr = addr;
+    ins
+}
+
+impl Lockstep {
+    pub fn new(a: ProgramState, b: ProgramState) -> Lockstep {
+        Lockstep {
+            a,
+            b,
+            compare_writes: true,
+            compare_inputs: false,
+            max_steps: 100000000,
+            steps: 0,
+            trace: VecDeque::new(),
+            history: [HashMap::new(), HashMap::new()],
+        }
+    }
+
+    // Runs until the machines diverge, both stop the same way, or max_steps
+    // is reached. Once both wait for input, more can be pushed to each and
+    // run called again.
+    pub fn run(&mut self) -> Option<Divergence> {
+        while self.steps < self.max_steps {
+            let pc = self.a.pc;
+            if self.b.pc != pc {
+                // Only after a caller moved one of them.
+                return Some(self.diverged(pc, Difference::Pc(self.a.pc, self.b.pc), vec![]));
+            }
+            let op = decode(self.a.program[pc]).map(|d| d.0);
+            let (reads_a, write_a) = accesses(&self.a);
+            let (reads_b, write_b) = accesses(&self.b);
+            let mut addresses: Vec<usize> = reads_a
+                .iter()
+                .zip(&reads_b)
+                .filter(|(x, y)| x != y || self.a.program[**x] != self.b.program[**y])
+                .flat_map(|(x, y)| vec![*x, *y])
+                .collect();
+
+            let (steps_a, steps_b) = (self.a.steps, self.b.steps);
+            let yielded_a = step_program(&mut self.a);
+            let yielded_b = step_program(&mut self.b);
+            let ran_a = self.a.steps > steps_a;
+            let ran_b = self.b.steps > steps_b;
+
+            match (stopped(&self.a, yielded_a), stopped(&self.b, yielded_b)) {
+                (true, true) if self.a.return_state == self.b.return_state => {
+                    if ran_a && ran_b {
+                        self.steps += 1;
+                    }
+                    return None;
+                }
+                (false, false) => {}
+                _ => {
+                    let d = Difference::Stopped(
+                        self.a.return_state.clone(),
+                        self.b.return_state.clone(),
+                    );
+                    return Some(self.diverged(pc, d, addresses));
+                }
+            }
+
+            let written_a = write_a.map(|w| (w, self.a.program[w]));
+            let written_b = write_b.map(|w| (w, self.b.program[w]));
+            for (i, w) in [written_a, written_b].iter().enumerate() {
+                if let Some((addr, value)) = w {
+                    self.history[i]
+                        .entry(*addr)
+                        .or_default()
+                        .push((self.steps, *value));
+                }
+            }
+
+            let difference =
+                if op == Some(Opcode::Output) && self.a.outputs.last() != self.b.outputs.last() {
+                    Some(Difference::Output(
+                        *self.a.outputs.last().unwrap(),
+                        *self.b.outputs.last().unwrap(),
+                    ))
+                } else if self.compare_writes
+                    && (op != Some(Opcode::Input) || self.compare_inputs)
+                    && written_a != written_b
+                {
+                    addresses.extend(write_a.iter().chain(&write_b));
+                    Some(Difference::Write(written_a, written_b))
+                } else if self.a.pc != self.b.pc {
+                    Some(Difference::Pc(self.a.pc, self.b.pc))
+                } else {
+                    None
+                };
+            if let Some(d) = difference {
+                return Some(self.diverged(pc, d, addresses));
+            }
+
+            self.steps += 1;
+            self.trace.push_back(pc);
+            if self.trace.len() > TRACE_LEN {
+                self.trace.pop_front();
+            }
+        }
+        None
+    }
+
+    fn diverged(&self, pc: usize, difference: Difference, mut addresses: Vec<usize>) -> Divergence {
+        addresses.sort_unstable();
+        addresses.dedup();
+        Divergence {
+            step: self.steps,
+            pc,
+            difference,
+            addresses,
+        }
+    }
+
+    // (step, value) for each write to addr in machine 0 (a) or 1 (b).
+    pub fn history(&self, machine: usize, addr: usize) -> &[(u64, i64)] {
+        match self.history[machine].get(&addr) {
+            Some(h) => h,
+            None => &[],
+        }
+    }
+
+    // The divergence with the instructions leading up to it, where each
+    // machine goes next, and the history of the addresses involved.
+    pub fn report(&self, d: &Divergence) -> String {
+        let mut res = String::new();
+        let _ = write!(res, "diverged after {} steps at {}: ", d.step, d.pc);
+        let _ = match &d.difference {
+            Difference::Pc(a, b) => writeln!(res, "a jumps to {}, b to {}", a, b),
+            Difference::Write(a, b) => {
+                let w = |w: &Option<(usize, i64)>| match w {
+                    Some((addr, v)) => format!("[{}] = {}", addr, v),
+                    None => "nothing".to_owned(),
+                };
+                writeln!(res, "a writes {}, b writes {}", w(a), w(b))
+            }
+            Difference::Output(a, b) => writeln!(res, "a outputs {}, b outputs {}", a, b),
+            Difference::Stopped(a, b) => writeln!(res, "a {:?}, b {:?}", a, b),
+        };
+
+        for pc in &self.trace {
+            let _ = writeln!(res, "  {:>6}  {}", pc, instruction(&self.a, *pc));
+        }
+        // Self-modifying code can leave different instructions at the same
+        // address, as in day 5 where the input patches the next jump.
+        let (ins_a, ins_b) = (instruction(&self.a, d.pc), instruction(&self.b, d.pc));
+        if ins_a == ins_b {
+            let _ = writeln!(res, "> {:>6}  {}", d.pc, ins_a);
+        } else {
+            let _ = writeln!(res, "> {:>6}  a: {}, b: {}", d.pc, ins_a, ins_b);
+        }
+        for (name, s) in [("a", &self.a), ("b", &self.b)] {
+            let _ = write!(res, "{} continues at {}:", name, s.pc);
+            let mut pc = s.pc;
+            for _ in 0..3 {
+                if pc >= s.program.len() {
+                    break;
+                }
+                let ins = instruction(s, pc);
+                let _ = write!(res, " {};", ins);
+                pc += ins.len();
+            }
+            res.push('\n');
+        }
+
+        for addr in &d.addresses {
+            let _ = writeln!(res, "[{}]", addr);
+            for (i, name) in ["a", "b"].iter().enumerate() {
+                let s = if i == 0 { &self.a } else { &self.b };
+                let writes: Vec<String> = self
+                    .history(i, *addr)
+                    .iter()
+                    .map(|(step, v)| format!("{}@{}", v, step))
+                    .collect();
+                let _ = writeln!(
+                    res,
+                    "  {}: now {}, writes {}",
+                    name,
+                    s.program[*addr],
+                    if writes.is_empty() {
+                        "none".to_owned()
+                    } else {
+                        writes.join(" ")
+                    }
+                );
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    fn machines(program: &str, a: i64, b: i64) -> Lockstep {
+        let program = parse_program(program);
+        let mut a_state = ProgramState::new(program.clone());
+        a_state.inputs.push(a);
+        let mut b_state = ProgramState::new(program);
+        b_state.inputs.push(b);
+        Lockstep::new(a_state, b_state)
+    }
+
+    #[test]
+    fn test_run() {
+        // Day 5's example that prints 999, 1000 or 1001 as the input is
+        // below, at or above 8. Both compare the input with 8 and store 0,
+        // then "less than" stores different flags.
+        let example = include_str!("../../05/test_b_7.txt");
+        let mut l = machines(example, 7, 9);
+        let d = l.run().unwrap();
+        assert_eq!(d.pc, 9);
+        assert_eq!(
+            d.difference,
+            Difference::Write(Some((20, 0)), Some((20, 1)))
+        );
+        assert_eq!(d.addresses, [20, 21]);
+        assert_eq!(l.history(0, 20), [(1, 0), (3, 0)]);
+        let report = l.report(&d);
+        assert!(report
+            .starts_with("diverged after 3 steps at 9: a writes [20] = 0, b writes [20] = 1\n"));
+        assert!(report.contains(">      9  lt 8, [21], [20]\n"));
+        assert!(report.contains("  a: now 7, writes 7@0\n"));
+
+        // Ignoring writes, the first difference is the jump on that flag.
+        let mut l = machines(example, 7, 9);
+        l.compare_writes = false;
+        let d = l.run().unwrap();
+        assert_eq!((d.pc, d.difference), (13, Difference::Pc(31, 16)));
+        assert_eq!(d.addresses, [20]);
+
+        let mut l = machines(example, 8, 8);
+        l.compare_inputs = true;
+        assert_eq!(l.run(), None);
+        assert_eq!(l.a.outputs, [1000]);
+        assert_eq!(l.a.return_state, ReturnState::Break);
+
+        let mut l = machines(example, 7, 8);
+        l.compare_inputs = true;
+        let d = l.run().unwrap();
+        assert_eq!((d.step, d.pc), (0, 0));
+
+        // The full diagnostic: test mode 1 and the thermal radiator, 5.
+        let mut l = machines(include_str!("../../05/input.txt"), 1, 5);
+        l.compare_writes = false;
+        let d = l.run().unwrap();
+        assert!(matches!(d.difference, Difference::Pc(..)));
+        assert!(l.a.outputs.is_empty() || l.a.outputs == l.b.outputs);
+
+        // A machine that stops early.
+        let mut l = machines("3,0,1005,0,6,99,104,1,99", 0, 1);
+        let d = l.run().unwrap();
+        assert_eq!(d.difference, Difference::Pc(5, 6));
+        let d = Lockstep::new(
+            ProgramState::new(parse_program("3,0,99")),
+            ProgramState::new(parse_program("99")),
+        )
+        .run()
+        .unwrap();
+        assert_eq!(
+            d.difference,
+            Difference::Stopped(ReturnState::NeedMoreInput, ReturnState::Break)
+        );
+    }
+}
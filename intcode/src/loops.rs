@@ -0,0 +1,171 @@
+// Livelock detection. The detector keeps a hash of memory that is updated
+// on every write, and at each backward jump combines it with pc and
+// relative_base. If a state hash comes back with no input read and no output
+// written in between, the machine is going around a loop it can never leave.
+//
+// Snapshots are taken Brent style, at the 1st, 2nd, 4th, 8th.. backward jump
+// after the last I/O, so only one state hash is kept and a loop is reported
+// within two trips around it. The snapshot also keeps the full state, a
+// cheap copy-on-write clone of memory, so that a matching hash is confirmed
+// before a loop is reported.
+
+use crate::memory::Memory;
+
+#[derive(Debug, PartialEq, Clone)]
+struct Snapshot {
+    hash: u64,
+    memory: Memory,
+    pc: usize,
+    relative_base: i64,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct LoopDetector {
+    memory_hash: u64,
+    snapshot: Option<Snapshot>,
+    // (inputs read, outputs written) when the snapshot was taken.
+    io: (usize, usize),
+    jumps: u64,
+    next_snapshot: u64,
+    lowest_pc: usize,
+    highest_pc: usize,
+}
+
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn cell_hash(addr: usize, value: i64) -> u64 {
+    mix((addr as u64).wrapping_mul(0x9e3779b97f4a7c15) ^ value as u64)
+}
+
+impl LoopDetector {
+    pub fn new() -> LoopDetector {
+        LoopDetector::default()
+    }
+
+    // Forgets the snapshot, for when the I/O counts it was taken at no
+    // longer mean the same thing.
+    pub(crate) fn restart(&mut self) {
+        self.snapshot = None;
+    }
+
+    pub(crate) fn write(&mut self, addr: usize, old: i64, new: i64) {
+        self.memory_hash ^= cell_hash(addr, old) ^ cell_hash(addr, new);
+    }
+
+    pub(crate) fn executed(&mut self, pc: usize) {
+        self.lowest_pc = self.lowest_pc.min(pc);
+        self.highest_pc = self.highest_pc.max(pc);
+    }
+
+    // Called after a jump to pc that did not go forward. Returns the first
+    // and last instruction address of the loop once the state repeats.
+    pub(crate) fn backward_jump(
+        &mut self,
+        memory: &Memory,
+        pc: usize,
+        relative_base: i64,
+        io: (usize, usize),
+    ) -> Option<(usize, usize)> {
+        let hash = mix(self.memory_hash ^ mix(pc as u64 ^ mix(relative_base as u64)));
+
+        let snapshot = match &self.snapshot {
+            Some(snapshot) if self.io == io => snapshot,
+            _ => {
+                self.io = io;
+                self.next_snapshot = 1;
+                self.take_snapshot(hash, memory, pc, relative_base);
+                return None;
+            }
+        };
+
+        if snapshot.hash == hash
+            && snapshot.pc == pc
+            && snapshot.relative_base == relative_base
+            && snapshot.memory.differences(memory).is_empty()
+        {
+            return Some((self.lowest_pc, self.highest_pc));
+        }
+
+        self.jumps += 1;
+        if self.jumps == self.next_snapshot {
+            self.next_snapshot *= 2;
+            self.take_snapshot(hash, memory, pc, relative_base);
+        }
+        None
+    }
+
+    fn take_snapshot(&mut self, hash: u64, memory: &Memory, pc: usize, relative_base: i64) {
+        self.snapshot = Some(Snapshot {
+            hash,
+            memory: memory.clone(),
+            pc,
+            relative_base,
+        });
+        self.jumps = 0;
+        self.lowest_pc = pc;
+        self.highest_pc = pc;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, run_program, ProgramState, ReturnState};
+
+    fn run(program: &str, inputs: Vec<i64>) -> ProgramState {
+        let mut s = ProgramState::new(parse_program(program));
+        s.inputs = inputs;
+        s.detect_loops();
+        while s.return_state == ReturnState::ProducedOutput && s.outputs.len() < 100 {
+            run_program(&mut s);
+        }
+        s
+    }
+
+    #[test]
+    fn test_run() {
+        assert_eq!(
+            run("1105,1,0", vec![]).return_state,
+            ReturnState::InfiniteLoop { start: 0, end: 0 }
+        );
+
+        // Flips [20] between 0 and 1 forever, so the state repeats every
+        // second trip.
+        assert_eq!(
+            run("1008,20,0,20,1105,1,0", vec![]).return_state,
+            ReturnState::InfiniteLoop { start: 0, end: 4 }
+        );
+
+        // Prints a line, then spins after its last output.
+        let s = run("104,1,104,2,1101,0,0,20,1105,1,4", vec![]);
+        assert_eq!(s.outputs, [1, 2]);
+        assert_eq!(
+            s.return_state,
+            ReturnState::InfiniteLoop { start: 4, end: 8 }
+        );
+
+        // Counting down to zero is not a loop, and neither is a loop that
+        // keeps producing output or reading input.
+        assert_eq!(
+            run("1101,1000,0,20,1001,20,-1,20,1005,20,4,99", vec![]).return_state,
+            ReturnState::Break
+        );
+        assert_eq!(run("104,1,1105,1,0", vec![]).outputs.len(), 100);
+        let s = run("3,20,1105,1,0", vec![5; 50]);
+        assert_eq!(s.return_state, ReturnState::NeedMoreInput);
+        assert_eq!(s.input_counter, 50);
+
+        // Memory changed behind the detector's back hashes the same, and
+        // is still not taken for a repeat.
+        let mut d = LoopDetector::new();
+        let mut memory = Memory::from(vec![0; 10]);
+        assert_eq!(d.backward_jump(&memory, 0, 0, (0, 0)), None);
+        memory[5] = 1;
+        assert_eq!(d.backward_jump(&memory, 0, 0, (0, 0)), None);
+        assert_eq!(d.backward_jump(&memory, 0, 0, (0, 0)), Some((0, 0)));
+    }
+}
@@ -0,0 +1,237 @@
+// Machine memory backed by a shared, read-only program image. Pages are
+// copied out of the image the first time they are written, so any number
+// of machines can run from one image without each paying for the
+// 1,000,000 cell copy.
+//
+// Written pages are reference counted too, and so is the page table, which
+// is split into chunks of pages. Cloning a Memory copies one pointer; the
+// first write afterwards copies the table's top level, one chunk and one
+// page, and a page shared between clones is copied again by whichever
+// clone writes to it first.
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+const PAGE_SHIFT: usize = 10;
+const PAGE_SIZE: usize = 1 << PAGE_SHIFT;
+const PAGE_MASK: usize = PAGE_SIZE - 1;
+const CHUNK_SHIFT: usize = 5;
+const CHUNK_SIZE: usize = 1 << CHUNK_SHIFT;
+const CHUNK_MASK: usize = CHUNK_SIZE - 1;
+
+type Page = Arc<Vec<i64>>;
+type Chunk = Arc<Vec<Option<Page>>>;
+
+#[derive(Clone)]
+pub struct Memory {
+    image: Arc<Vec<i64>>,
+    chunks: Arc<Vec<Option<Chunk>>>,
+}
+
+impl Memory {
+    pub fn new(image: Arc<Vec<i64>>) -> Memory {
+        let page_count = (image.len() + PAGE_SIZE - 1) >> PAGE_SHIFT;
+        let chunk_count = (page_count + CHUNK_SIZE - 1) >> CHUNK_SHIFT;
+        Memory {
+            image,
+            chunks: Arc::new(vec![None; chunk_count]),
+        }
+    }
+
+    fn page_count(&self) -> usize {
+        (self.len() + PAGE_SIZE - 1) >> PAGE_SHIFT
+    }
+
+    fn page(&self, n: usize) -> Option<&Page> {
+        self.chunks[n >> CHUNK_SHIFT].as_ref()?[n & CHUNK_MASK].as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.image.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.image.is_empty()
+    }
+
+    pub fn image(&self) -> &Arc<Vec<i64>> {
+        &self.image
+    }
+
+    pub fn dirty_pages(&self) -> usize {
+        self.chunks
+            .iter()
+            .flatten()
+            .map(|c| c.iter().filter(|p| p.is_some()).count())
+            .sum()
+    }
+
+    // Whether the page holding cell i has been written.
+    pub fn is_dirty(&self, i: usize) -> bool {
+        self.page(i >> PAGE_SHIFT).is_some()
+    }
+
+    // Written pages that are not shared with any clone.
+    pub fn private_pages(&self) -> usize {
+        if Arc::strong_count(&self.chunks) > 1 {
+            return 0;
+        }
+        self.chunks
+            .iter()
+            .flatten()
+            .filter(|c| Arc::strong_count(c) == 1)
+            .map(|c| {
+                c.iter()
+                    .flatten()
+                    .filter(|page| Arc::strong_count(page) == 1)
+                    .count()
+            })
+            .sum()
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        (0..self.len()).map(|i| self[i]).collect()
+    }
+
+    pub fn range(&self, start: usize, end: usize) -> Vec<i64> {
+        (start..end).map(|i| self[i]).collect()
+    }
+
+    // Addresses where self and other hold different values, in order. Pages
+    // the two still share with each other or with a common image are
+    // skipped without comparing cells.
+    pub fn differences(&self, other: &Memory) -> Vec<usize> {
+        let mut res = Vec::new();
+        let same_image = Arc::ptr_eq(&self.image, &other.image);
+
+        let pages = self.page_count();
+        for n in 0..pages.max(other.page_count()) {
+            let shared = n < pages.min(other.page_count())
+                && match (self.page(n), other.page(n)) {
+                    (None, None) => same_image,
+                    (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                    _ => false,
+                };
+            if shared {
+                continue;
+            }
+            let start = n << PAGE_SHIFT;
+            let end = (start + PAGE_SIZE).min(self.len().max(other.len()));
+            for i in start..end {
+                if self.get(i) != other.get(i) {
+                    res.push(i);
+                }
+            }
+        }
+
+        res
+    }
+
+    // Cells past the end read as zero, like unwritten memory.
+    fn get(&self, i: usize) -> i64 {
+        if i < self.len() {
+            self[i]
+        } else {
+            0
+        }
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(program: Vec<i64>) -> Memory {
+        Memory::new(Arc::new(program))
+    }
+}
+
+impl Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, i: usize) -> &i64 {
+        match self.page(i >> PAGE_SHIFT) {
+            Some(page) => &page[i & PAGE_MASK],
+            None => &self.image[i],
+        }
+    }
+}
+
+impl IndexMut<usize> for Memory {
+    fn index_mut(&mut self, i: usize) -> &mut i64 {
+        let n = i >> PAGE_SHIFT;
+        let image = &self.image;
+        let chunk = Arc::make_mut(&mut self.chunks)[n >> CHUNK_SHIFT]
+            .get_or_insert_with(|| Arc::new(vec![None; CHUNK_SIZE]));
+        let page = Arc::make_mut(chunk)[n & CHUNK_MASK].get_or_insert_with(|| {
+            let start = n << PAGE_SHIFT;
+            let end = (start + PAGE_SIZE).min(image.len());
+            Arc::new(image[start..end].to_vec())
+        });
+        &mut Arc::make_mut(page)[i & PAGE_MASK]
+    }
+}
+
+impl PartialEq for Memory {
+    fn eq(&self, other: &Memory) -> bool {
+        self.len() == other.len() && (0..self.len()).all(|i| self[i] == other[i])
+    }
+}
+
+impl fmt::Debug for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Memory {{ len: {}, dirty_pages: {} }}",
+            self.len(),
+            self.dirty_pages()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let image = Arc::new((0..3000).collect::<Vec<i64>>());
+        let mut a = Memory::new(image.clone());
+        let b = Memory::new(image);
+
+        a[2047] = -1;
+        assert_eq!(a[2047], -1);
+        assert_eq!(a[2048], 2048);
+        assert_eq!(b[2047], 2047);
+        assert_eq!(a.dirty_pages(), 1);
+        assert_eq!(b.dirty_pages(), 0);
+        assert!(a.is_dirty(1024) && !a.is_dirty(2048));
+        assert!(a != b);
+
+        a[2999] = 2999;
+        assert_eq!(a.dirty_pages(), 2);
+        a[2047] = 2047;
+        assert_eq!(a, b);
+
+        let mut c = a.clone();
+        assert_eq!(c.private_pages(), 0);
+        c[2047] = 5;
+        assert_eq!(c.private_pages(), 1);
+        assert_eq!(a[2047], 2047);
+        assert_eq!(a.private_pages(), 1);
+
+        assert_eq!(a.differences(&c), [2047]);
+
+        // Pages in different chunks of the table.
+        let mut d = Memory::from(vec![0; 100 * PAGE_SIZE]);
+        d[5] = 1;
+        let mut e = d.clone();
+        e[90 * PAGE_SIZE] = 2;
+        assert_eq!(d.dirty_pages(), 1);
+        assert_eq!(e.dirty_pages(), 2);
+        assert_eq!((d.private_pages(), e.private_pages()), (0, 1));
+        assert_eq!(d.differences(&e), [90 * PAGE_SIZE]);
+        assert_eq!(
+            c.differences(&Memory::from(vec![0; 2])),
+            (1..3000).collect::<Vec<usize>>()
+        );
+    }
+}
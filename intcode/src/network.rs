@@ -0,0 +1,304 @@
+// Runs each machine on its own thread, with inputs and outputs wired
+// together through channels. A machine's input channel can be fed by any
+// number of machines (fan-in) and its outputs go to every machine it is
+// connected to (fan-out).
+//
+// When a machine halts it drops its senders. A machine waiting for input on
+// a channel that has no senders left can never run again, so it stops too;
+// that way halts travel down a pipeline or around a feedback loop.
+//
+// A machine connected to itself, or a loop nothing feeds, keeps its own
+// senders alive and would wait forever. run also returns once every machine
+// left is waiting for input and no value has arrived anywhere for a while,
+// like Scheduler::run_until when everything is parked.
+
+use crate::{step_program, ProgramState, ReturnState};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+struct Node {
+    state: ProgramState,
+    tx: Sender<i64>,
+    rx: Receiver<i64>,
+    outs: Vec<Sender<i64>>,
+}
+
+#[derive(Default)]
+pub struct Network {
+    nodes: Vec<Node>,
+}
+
+pub struct Running {
+    threads: Vec<JoinHandle<ProgramState>>,
+    stop: Arc<AtomicBool>,
+    activity: Arc<Activity>,
+}
+
+#[derive(Default)]
+struct Activity {
+    // Machines that haven't stopped.
+    live: AtomicUsize,
+    // Machines that have waited a whole timeout for input without any.
+    waiting: AtomicUsize,
+    // Values received by all machines.
+    received: AtomicU64,
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network::default()
+    }
+
+    pub fn add(&mut self, state: ProgramState) -> usize {
+        let (tx, rx) = channel();
+        self.nodes.push(Node {
+            state,
+            tx,
+            rx,
+            outs: Vec::new(),
+        });
+        self.nodes.len() - 1
+    }
+
+    // Every output of from also goes to the input of to.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        let tx = self.nodes[to].tx.clone();
+        self.nodes[from].outs.push(tx);
+    }
+
+    // Sender feeding a machine from outside. The machine's input only
+    // closes once this is dropped.
+    pub fn input(&self, to: usize) -> Sender<i64> {
+        self.nodes[to].tx.clone()
+    }
+
+    // Receiver collecting the outputs of the given machines.
+    pub fn tap(&mut self, from: &[usize]) -> Receiver<i64> {
+        let (tx, rx) = channel();
+        for id in from {
+            self.nodes[*id].outs.push(tx.clone());
+        }
+        rx
+    }
+
+    pub fn start(self) -> Running {
+        let stop = Arc::new(AtomicBool::new(false));
+        let activity = Arc::new(Activity::default());
+        activity.live.store(self.nodes.len(), Ordering::SeqCst);
+
+        let threads = self
+            .nodes
+            .into_iter()
+            .map(|node| {
+                let Node {
+                    state, rx, outs, ..
+                } = node;
+                let stop = stop.clone();
+                let activity = activity.clone();
+                thread::spawn(move || run_node(state, rx, outs, &stop, &activity))
+            })
+            .collect();
+
+        Running {
+            threads,
+            stop,
+            activity,
+        }
+    }
+
+    // Runs until every machine has stopped or is stuck waiting for input.
+    // Input sent from outside has to arrive without long pauses, or the
+    // machines waiting for it count as stuck.
+    pub fn run(self) -> Vec<ProgramState> {
+        let running = self.start();
+        running.wait_idle();
+        running.shutdown()
+    }
+}
+
+impl Running {
+    // Waits for every machine to stop. Final states are in the order the
+    // machines were added.
+    pub fn join(self) -> Vec<ProgramState> {
+        self.threads
+            .into_iter()
+            .map(|t| t.join().expect("machine thread panicked"))
+            .collect()
+    }
+
+    // Waits until every machine has stopped, or those left have all been
+    // waiting for input over a period in which no value arrived anywhere.
+    // Each of them checks its input more than once in that period, so no
+    // value can be left queued.
+    pub fn wait_idle(&self) {
+        let a = &self.activity;
+        let mut seen = None;
+        loop {
+            let live = a.live.load(Ordering::SeqCst);
+            if live == 0 {
+                return;
+            }
+            let received = a.received.load(Ordering::SeqCst);
+            if a.waiting.load(Ordering::SeqCst) < live {
+                seen = None;
+            } else if seen == Some(received) {
+                return;
+            } else {
+                seen = Some(received);
+            }
+            thread::sleep(IDLE_CHECK);
+        }
+    }
+
+    // Stops machines that are still running or waiting for input, then
+    // joins them.
+    pub fn shutdown(self) -> Vec<ProgramState> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.join()
+    }
+}
+
+const SLICE: u64 = 10000;
+const RECV_TIMEOUT: Duration = Duration::from_millis(50);
+const IDLE_CHECK: Duration = Duration::from_millis(120);
+
+fn run_node(
+    mut s: ProgramState,
+    rx: Receiver<i64>,
+    outs: Vec<Sender<i64>>,
+    stop: &AtomicBool,
+    activity: &Activity,
+) -> ProgramState {
+    let mut waiting = false;
+    while !stop.load(Ordering::Relaxed) {
+        match s.return_state {
+            ReturnState::Break | ReturnState::InfiniteLoop { .. } => break,
+            // Ran off the end of memory, as Scheduler::run_slice has it.
+            _ if s.pc >= s.program.len() => break,
+            ReturnState::NeedMoreInput if s.input_counter >= s.inputs.len() => {
+                match rx.recv_timeout(RECV_TIMEOUT) {
+                    Ok(v) => {
+                        activity.received.fetch_add(1, Ordering::SeqCst);
+                        if waiting {
+                            waiting = false;
+                            activity.waiting.fetch_sub(1, Ordering::SeqCst);
+                        }
+                        s.inputs.push(v);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        if !waiting {
+                            waiting = true;
+                            activity.waiting.fetch_add(1, Ordering::SeqCst);
+                        }
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            _ => {}
+        }
+
+        // Run in slices so a machine that never yields still sees the stop
+        // flag.
+        let limit = s.steps + SLICE;
+        s.return_state = ReturnState::ProducedOutput;
+        while s.steps < limit && s.pc < s.program.len() {
+            if step_program(&mut s) {
+                if s.return_state != ReturnState::ProducedOutput {
+                    break;
+                }
+                let v = *s.outputs.last().unwrap();
+                for tx in &outs {
+                    // A receiver that went away just stops listening.
+                    let _ = tx.send(v);
+                }
+            }
+        }
+    }
+
+    if waiting {
+        activity.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+    activity.live.fetch_sub(1, Ordering::SeqCst);
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_run() {
+        // Day 7 feedback loop, amplifier E feeding back into A.
+        let amp = parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        );
+        let mut net = Network::new();
+        let amps: Vec<usize> = [9, 8, 7, 6, 5]
+            .iter()
+            .map(|phase| {
+                let mut s = ProgramState::new(amp.clone());
+                s.inputs.push(*phase);
+                net.add(s)
+            })
+            .collect();
+        for i in 0..5 {
+            net.connect(amps[i], amps[(i + 1) % 5]);
+        }
+        net.input(amps[0]).send(0).unwrap();
+        let states = net.run();
+        assert_eq!(states[4].return_state, ReturnState::Break);
+        assert_eq!(*states[4].outputs.last().unwrap(), 139629729);
+
+        // Fan-out from one source to two workers, fan-in to one tap.
+        let mut net = Network::new();
+        let source = net.add(ProgramState::new(parse_program("104,1,104,2,104,3,99")));
+        let double = net.add(ProgramState::new(parse_program(
+            "3,20,1002,20,2,20,4,20,1105,1,0",
+        )));
+        let triple = net.add(ProgramState::new(parse_program(
+            "3,20,1002,20,3,20,4,20,1105,1,0",
+        )));
+        net.connect(source, double);
+        net.connect(source, triple);
+        let tap = net.tap(&[double, triple]);
+        let states = net.run();
+        let mut values: Vec<i64> = tap.iter().collect();
+        values.sort_unstable();
+        assert_eq!(values, [2, 3, 4, 6, 6, 9]);
+        assert_eq!(states[1].return_state, ReturnState::NeedMoreInput);
+
+        // Nothing ever arrives for a machine reading its own outputs, or for
+        // a loop of echoes with no input from outside.
+        let mut net = Network::new();
+        let me = net.add(ProgramState::new(parse_program("3,20,1105,1,0")));
+        net.connect(me, me);
+        let echo = parse_program("3,20,4,20,1105,1,0");
+        let a = net.add(ProgramState::new(echo.clone()));
+        let b = net.add(ProgramState::new(echo));
+        net.connect(a, b);
+        net.connect(b, a);
+        let states = net.run();
+        assert!(states
+            .iter()
+            .all(|s| s.return_state == ReturnState::NeedMoreInput));
+
+        // A machine that runs off the end of memory stops there.
+        let mut net = Network::new();
+        let last = net.add(ProgramState::new(vec![104, 5]));
+        let tap = net.tap(&[last]);
+        let states = net.run();
+        assert_eq!(tap.iter().collect::<Vec<_>>(), [5]);
+        assert_eq!(states[0].pc, 2);
+
+        // A machine that spins forever stops on shutdown.
+        let mut net = Network::new();
+        net.add(ProgramState::new(parse_program("1105,1,0")));
+        let states = net.start().shutdown();
+        assert_ne!(states[0].return_state, ReturnState::Break);
+    }
+}
@@ -0,0 +1,336 @@
+// Resource limits for running programs that can't be trusted to behave,
+// like fuzzed programs or generated springscript experiments. Each
+// instruction is checked before it runs, so a machine that hits a limit is
+// left exactly as it was and can be resumed with a larger one. Anything
+// step_program would panic on is stopped the same way.
+//
+// On a machine with a registry, instructions are decoded by the registry
+// and their operands checked like any other; jumps are checked as their
+// Flow declares. Overflow and the input and output quotas only apply to
+// machines running the built-ins.
+
+use crate::disasm::decode;
+use crate::registry::{operands, Flow, OperandError, Param};
+use crate::{step_program, Opcode, ProgramState};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// The clock is read once per this many steps.
+const CLOCK_INTERVAL: u64 = 1024;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Limit {
+    // An operand addressed this cell: negative, past the end of memory or
+    // above max_address.
+    Address(i64),
+    // A cell that doesn't decode, or writes through an immediate operand.
+    BadInstruction(i64),
+    // A jump to a negative address or past the end of memory.
+    Jump(i64),
+    // Addition, multiplication or an address that doesn't fit in an i64.
+    Overflow,
+    Pages,
+    Outputs,
+    Inputs,
+    Time,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct QuotaExceeded {
+    pub limit: Limit,
+    pub pc: usize,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.limit {
+            Limit::Address(addr) => write!(f, "address {} out of bounds", addr)?,
+            Limit::BadInstruction(n) => write!(f, "bad instruction {}", n)?,
+            Limit::Jump(addr) => write!(f, "jump to {} out of bounds", addr)?,
+            Limit::Overflow => write!(f, "arithmetic overflow")?,
+            Limit::Pages => write!(f, "page quota exceeded")?,
+            Limit::Outputs => write!(f, "output quota exceeded")?,
+            Limit::Inputs => write!(f, "input quota exceeded")?,
+            Limit::Time => write!(f, "time quota exceeded")?,
+        }
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Quotas {
+    // Highest address any operand may use.
+    pub max_address: Option<usize>,
+    // Written pages of memory (see Memory::dirty_pages).
+    pub max_pages: Option<usize>,
+    // Outputs waiting in s.outputs; a host that drains them can run on.
+    pub max_outputs: Option<usize>,
+    // Inputs consumed over the machine's life.
+    pub max_inputs: Option<usize>,
+    pub max_time: Option<Duration>,
+    // Time spent in run_program so far, counted against max_time.
+    pub elapsed: Duration,
+}
+
+impl Quotas {
+    pub fn new() -> Quotas {
+        Quotas::default()
+    }
+
+    fn check(&self, s: &ProgramState) -> Result<(), Limit> {
+        let n = s.program[s.pc];
+        let ops = operands(s, self.max_address.unwrap_or(usize::MAX)).map_err(|e| match e {
+            OperandError::BadInstruction(_) | OperandError::ImmediateWrite(_) => {
+                Limit::BadInstruction(n)
+            }
+            OperandError::PastEnd(addr) => Limit::Address(addr as i64),
+            OperandError::Address(_, addr) => Limit::Address(addr),
+            OperandError::Overflow => Limit::Overflow,
+        })?;
+        // What the instruction does is only known for built-ins; registered
+        // ones get the operand checks and have their jumps checked by Flow.
+        let (op, flow) = match &s.registry {
+            Some(r) => (None, r.get(n % 100).map_or(Flow::Unknown, |d| d.flow)),
+            None => {
+                let op = decode(n).map(|d| d.0);
+                let branch = |if_nonzero| Flow::Branch {
+                    cond: 0,
+                    target: 1,
+                    if_nonzero,
+                };
+                let flow = match op {
+                    Some(Opcode::JumpIfTrue) => branch(true),
+                    Some(Opcode::JumpIfFalse) => branch(false),
+                    _ => Flow::Next,
+                };
+                (op, flow)
+            }
+        };
+        let len = s.program.len();
+
+        // Operand values, read the way step_program will.
+        let mut values = ops.raw;
+        for (i, param) in ops.params.iter().enumerate() {
+            let addr = match ops.addrs[i] {
+                Some(addr) => addr,
+                None => continue,
+            };
+            if *param == Param::Write && !s.program.is_dirty(addr) {
+                if let Some(max) = self.max_pages {
+                    if s.program.dirty_pages() >= max {
+                        return Err(Limit::Pages);
+                    }
+                }
+            }
+            values[i] = s.program[addr];
+        }
+
+        let jump = match flow {
+            Flow::Branch {
+                cond,
+                target,
+                if_nonzero,
+            } if (values[cond] != 0) == if_nonzero => Some(values[target]),
+            _ => None,
+        };
+        if let Some(target) = jump {
+            if target < 0 || target as usize >= len {
+                return Err(Limit::Jump(target));
+            }
+        }
+        let overflow = match op {
+            Some(Opcode::Addition) => values[0].checked_add(values[1]).is_none(),
+            Some(Opcode::Multiplication) => values[0].checked_mul(values[1]).is_none(),
+            Some(Opcode::AdjustRelativeBase) => s.relative_base.checked_add(values[0]).is_none(),
+            _ => false,
+        };
+        if overflow {
+            return Err(Limit::Overflow);
+        }
+
+        let full = |count: usize, max: Option<usize>| max.is_some_and(|max| count >= max);
+        match op {
+            Some(Opcode::Output) if full(s.outputs.len(), self.max_outputs) => Err(Limit::Outputs),
+            // Waiting for input is fine, reading it is not.
+            Some(Opcode::Input)
+                if s.input_counter < s.inputs.len() && full(s.input_counter, self.max_inputs) =>
+            {
+                Err(Limit::Inputs)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Drop-in replacement for run_program that stops before any instruction
+    // that would go over a limit or would panic. It never returns Ok with pc
+    // outside memory.
+    pub fn run_program(&mut self, s: &mut ProgramState) -> Result<(), QuotaExceeded> {
+        let start = Instant::now();
+        let elapsed = self.elapsed;
+        let mut res = Ok(());
+
+        let mut steps = 0;
+        while s.pc < s.program.len() {
+            steps += 1;
+            if steps % CLOCK_INTERVAL == 0 {
+                self.elapsed = elapsed + start.elapsed();
+                if self.max_time.is_some_and(|max| self.elapsed > max) {
+                    res = Err(Limit::Time);
+                    break;
+                }
+            }
+            if let Err(limit) = self.check(s) {
+                res = Err(limit);
+                break;
+            }
+            if step_program(s) {
+                break;
+            }
+        }
+        if res.is_ok() && s.pc >= s.program.len() {
+            res = Err(Limit::Address(s.pc as i64));
+        }
+
+        self.elapsed = elapsed + start.elapsed();
+        res.map_err(|limit| QuotaExceeded { limit, pc: s.pc })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{Effect, OpcodeDef, Registry};
+    use crate::{parse_program, ReturnState};
+    use std::sync::Arc;
+
+    fn run(
+        quotas: &mut Quotas,
+        program: &str,
+        inputs: Vec<i64>,
+    ) -> Result<ProgramState, QuotaExceeded> {
+        let mut s = ProgramState::new(parse_program(program));
+        s.inputs = inputs;
+        while s.return_state == ReturnState::ProducedOutput {
+            quotas.run_program(&mut s)?;
+        }
+        Ok(s)
+    }
+
+    #[test]
+    fn test_run() {
+        let mut generous = Quotas {
+            max_address: Some(2000),
+            max_pages: Some(2),
+            max_outputs: Some(1),
+            max_inputs: Some(1),
+            max_time: Some(Duration::from_secs(60)),
+            ..Quotas::new()
+        };
+        let s = run(&mut generous, include_str!("../../09/input"), vec![1]).unwrap();
+        assert_eq!(s.return_state, ReturnState::Break);
+        assert!(generous.elapsed > Duration::from_secs(0));
+
+        // Unpadded programs: instructions at the very end of memory run,
+        // ones whose operands would be past it don't.
+        let mut s = ProgramState::new(vec![99]);
+        assert_eq!(Quotas::new().run_program(&mut s), Ok(()));
+        assert_eq!(s.return_state, ReturnState::Break);
+        let mut s = ProgramState::new(vec![104, 7, 99]);
+        while s.return_state != ReturnState::Break {
+            Quotas::new().run_program(&mut s).unwrap();
+        }
+        assert_eq!(s.outputs, [7]);
+        let err = Quotas::new()
+            .run_program(&mut ProgramState::new(vec![1101, 1, 1]))
+            .unwrap_err();
+        assert_eq!((err.limit, err.pc), (Limit::Address(3), 0));
+
+        // Writes to 10^15 instead of panicking.
+        let err = run(&mut Quotas::new(), "1101,1,1,1000000000000000,99", vec![]).unwrap_err();
+        assert_eq!(err.limit, Limit::Address(1000000000000000));
+        assert_eq!(
+            err.to_string(),
+            "address 1000000000000000 out of bounds at pc 0"
+        );
+        let mut quotas = Quotas {
+            max_address: Some(100),
+            ..Quotas::new()
+        };
+        let err = run(&mut quotas, "109,-5,204,106,99", vec![]).unwrap_err();
+        assert_eq!((err.limit, err.pc), (Limit::Address(101), 2));
+
+        // Fills memory a page at a time.
+        let mut quotas = Quotas {
+            max_pages: Some(3),
+            ..Quotas::new()
+        };
+        let err = run(
+            &mut quotas,
+            "1101,0,0,0,109,1024,21101,1,0,0,1105,1,4",
+            vec![],
+        )
+        .unwrap_err();
+        assert_eq!(err.limit, Limit::Pages);
+
+        // Prints forever; drained outputs don't count.
+        let mut quotas = Quotas {
+            max_outputs: Some(10),
+            ..Quotas::new()
+        };
+        let mut s = ProgramState::new(parse_program("104,1,1105,1,0"));
+        while quotas.run_program(&mut s).is_ok() {}
+        assert_eq!(s.outputs.len(), 10);
+        s.outputs.truncate(5);
+        let mut produced = 0;
+        while quotas.run_program(&mut s).is_ok() {
+            produced += 1;
+        }
+        assert_eq!(produced, 5);
+
+        let mut quotas = Quotas {
+            max_inputs: Some(2),
+            ..Quotas::new()
+        };
+        let err = run(&mut quotas, "3,0,3,0,3,0,99", vec![1, 2, 3]).unwrap_err();
+        assert_eq!((err.limit, err.pc), (Limit::Inputs, 4));
+
+        let mut quotas = Quotas {
+            max_time: Some(Duration::from_millis(20)),
+            ..Quotas::new()
+        };
+        let err = run(&mut quotas, "1105,1,0", vec![]).unwrap_err();
+        assert_eq!(err.limit, Limit::Time);
+        assert!(quotas.elapsed >= Duration::from_millis(20));
+
+        // Instructions step_program would panic on, or run off the end of
+        // memory with.
+        let limit = |program: &str| run(&mut Quotas::new(), program, vec![]).unwrap_err().limit;
+        assert_eq!(limit("42,0,0,0,99"), Limit::BadInstruction(42));
+        assert_eq!(limit("1101,0,55,4,0"), Limit::BadInstruction(55));
+        assert_eq!(limit("11101,1,1,0,99"), Limit::BadInstruction(11101));
+        assert_eq!(limit("1105,1,-5"), Limit::Jump(-5));
+        assert_eq!(limit("1106,0,1000000"), Limit::Jump(1000000));
+        assert_eq!(limit("1002,5,3,0,99,4611686018427387904"), Limit::Overflow);
+        assert_eq!(limit("1001,5,1,0,99,9223372036854775807"), Limit::Overflow);
+        assert_eq!(limit("109,9223372036854775807,109,1,99"), Limit::Overflow);
+        // A registered jump is checked through its Flow.
+        let mut r = Registry::builtin();
+        let jmp = OpcodeDef::new(50, "jmp", &[Param::Read], |a| {
+            Effect::Jump(a.read(0) as usize)
+        });
+        let always = Flow::Branch {
+            cond: 0,
+            target: 0,
+            if_nonzero: true,
+        };
+        r.register(jmp.with_flow(always)).unwrap();
+        let mut s = ProgramState::new(parse_program("150,-5"));
+        s.registry = Some(Arc::new(r));
+        let err = Quotas::new().run_program(&mut s).unwrap_err();
+        assert_eq!(err.limit, Limit::Jump(-5));
+
+        // Taken jumps are checked, untaken ones aren't.
+        let s = run(&mut Quotas::new(), "1105,0,-5,99", vec![]).unwrap();
+        assert_eq!(s.return_state, ReturnState::Break);
+    }
+}
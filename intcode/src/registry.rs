@@ -0,0 +1,534 @@
+// Opcode table for the VM. Registry::builtin() holds the ten standard
+// instructions as ordinary entries; register adds experimental ones, or
+// replaces a built-in, without forking the interpreter.
+//
+// A machine runs on a table when ProgramState::registry is set; then
+// step_program, and everything built on it (run_until, Scheduler, Network,
+// Quotas, the debugger), goes through Registry::step. Without one,
+// step_program runs the built-ins directly, which is the fast path, and
+// the two must agree on those.
+
+use crate::disasm::{decode, param_count, write_param};
+use crate::{
+    check_loop, digit_to_mode, read_param, write_index, Mode, Opcode, ProgramState, ReturnState,
+};
+use std::fmt;
+use std::sync::Arc;
+
+pub const MAX_PARAMS: usize = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Param {
+    // Any mode.
+    Read,
+    // Position or relative mode; immediate is an error.
+    Write,
+}
+
+// What the interpreter does after a handler returns.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Effect {
+    Next,
+    Jump(usize),
+    // Moves to the next instruction, then yields with the given state.
+    Yield(ReturnState),
+    // Yields NeedMoreInput without executing; the instruction runs again
+    // on the next call.
+    Wait,
+    // Stops at this instruction, like 99.
+    Halt,
+}
+
+// Where control can go after an instruction, as far as static tools like
+// lint need to know. It has to match the Effects the handler returns.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Flow {
+    // Effect::Next, Yield or Wait: on to the next instruction.
+    Next,
+    // Effect::Jump to the value of operand target when operand cond is
+    // nonzero (if_nonzero) or zero, Effect::Next otherwise.
+    Branch {
+        cond: usize,
+        target: usize,
+        if_nonzero: bool,
+    },
+    // Effect::Halt.
+    Halt,
+    // Jumps somewhere that can't be worked out from the operands.
+    Unknown,
+}
+
+// Operands of the instruction being executed, with the machine for
+// everything else (inputs, outputs, relative base).
+pub struct Args<'a> {
+    pub state: &'a mut ProgramState,
+    modes: [Mode; MAX_PARAMS],
+    raw: [i64; MAX_PARAMS],
+}
+
+impl<'a> Args<'a> {
+    pub fn read(&self, i: usize) -> i64 {
+        read_param(self.state, self.modes[i], self.raw[i])
+    }
+
+    pub fn write(&mut self, i: usize, value: i64) {
+        let index = write_index(self.state, self.modes[i], self.raw[i]);
+        self.state.write(index, value);
+    }
+}
+
+pub type Handler = Arc<dyn Fn(&mut Args) -> Effect + Send + Sync>;
+
+#[derive(Clone)]
+pub struct OpcodeDef {
+    pub code: i64,
+    pub mnemonic: String,
+    pub params: Vec<Param>,
+    pub flow: Flow,
+    pub handler: Handler,
+}
+
+impl OpcodeDef {
+    pub fn new<F>(code: i64, mnemonic: &str, params: &[Param], handler: F) -> OpcodeDef
+    where
+        F: Fn(&mut Args) -> Effect + Send + Sync + 'static,
+    {
+        OpcodeDef {
+            code,
+            mnemonic: mnemonic.to_owned(),
+            params: params.to_vec(),
+            flow: Flow::Next,
+            handler: Arc::new(handler),
+        }
+    }
+
+    pub fn with_flow(mut self, flow: Flow) -> OpcodeDef {
+        self.flow = flow;
+        self
+    }
+}
+
+// Handlers can't be compared, so the same handler means the same closure.
+impl PartialEq for OpcodeDef {
+    fn eq(&self, other: &OpcodeDef) -> bool {
+        self.code == other.code
+            && self.mnemonic == other.mnemonic
+            && self.params == other.params
+            && self.flow == other.flow
+            && Arc::ptr_eq(&self.handler, &other.handler)
+    }
+}
+
+impl fmt::Debug for OpcodeDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "OpcodeDef {{ {} {} {:?} }}",
+            self.code, self.mnemonic, self.params
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Registry {
+    table: Vec<Option<OpcodeDef>>,
+}
+
+fn binary<F: Fn(i64, i64) -> i64 + Send + Sync + 'static>(
+    code: i64,
+    mnemonic: &str,
+    f: F,
+) -> OpcodeDef {
+    use Param::*;
+    OpcodeDef::new(code, mnemonic, &[Read, Read, Write], move |a| {
+        let v = f(a.read(0), a.read(1));
+        a.write(2, v);
+        Effect::Next
+    })
+}
+
+impl Registry {
+    pub fn empty() -> Registry {
+        Registry {
+            table: vec![None; 100],
+        }
+    }
+
+    pub fn builtin() -> Registry {
+        use Param::*;
+
+        let mut r = Registry::empty();
+        let defs = vec![
+            binary(1, "add", |a, b| a + b),
+            binary(2, "mul", |a, b| a * b),
+            OpcodeDef::new(3, "in", &[Write], |a| {
+                let s = &mut *a.state;
+                if s.inputs.len() <= s.input_counter {
+                    return Effect::Wait;
+                }
+                let v = s.inputs[s.input_counter];
+                s.input_counter += 1;
+                s.input_steps.push(s.steps);
+                a.write(0, v);
+                Effect::Next
+            }),
+            OpcodeDef::new(4, "out", &[Read], |a| {
+                let v = a.read(0);
+                a.state.outputs.push(v);
+                Effect::Yield(ReturnState::ProducedOutput)
+            }),
+            OpcodeDef::new(5, "jt", &[Read, Read], |a| {
+                if a.read(0) != 0 {
+                    Effect::Jump(a.read(1) as usize)
+                } else {
+                    Effect::Next
+                }
+            })
+            .with_flow(Flow::Branch {
+                cond: 0,
+                target: 1,
+                if_nonzero: true,
+            }),
+            OpcodeDef::new(6, "jf", &[Read, Read], |a| {
+                if a.read(0) == 0 {
+                    Effect::Jump(a.read(1) as usize)
+                } else {
+                    Effect::Next
+                }
+            })
+            .with_flow(Flow::Branch {
+                cond: 0,
+                target: 1,
+                if_nonzero: false,
+            }),
+            binary(7, "lt", |a, b| (a < b) as i64),
+            binary(8, "eq", |a, b| (a == b) as i64),
+            OpcodeDef::new(9, "arb", &[Read], |a| {
+                a.state.relative_base += a.read(0);
+                Effect::Next
+            }),
+            OpcodeDef::new(99, "hlt", &[], |_| Effect::Halt).with_flow(Flow::Halt),
+        ];
+        for def in defs {
+            r.register(def).unwrap();
+        }
+        r
+    }
+
+    // Adds def, replacing whatever had its code. Mnemonics must stay unique
+    // so the assembler can find them.
+    pub fn register(&mut self, def: OpcodeDef) -> Result<(), String> {
+        if def.code < 1 || def.code > 99 {
+            return Err(format!("opcode {} is not in 1..=99", def.code));
+        }
+        if def.params.len() > MAX_PARAMS {
+            return Err(format!(
+                "{} takes {} parameters, at most {} are supported",
+                def.mnemonic,
+                def.params.len(),
+                MAX_PARAMS
+            ));
+        }
+        if let Some(other) = self.by_mnemonic(&def.mnemonic) {
+            if other.code != def.code {
+                return Err(format!(
+                    "mnemonic {} is already used by opcode {}",
+                    def.mnemonic, other.code
+                ));
+            }
+        }
+        let code = def.code as usize;
+        self.table[code] = Some(def);
+        Ok(())
+    }
+
+    pub fn get(&self, code: i64) -> Option<&OpcodeDef> {
+        if code < 0 {
+            return None;
+        }
+        self.table.get(code as usize).and_then(|d| d.as_ref())
+    }
+
+    pub fn by_mnemonic(&self, mnemonic: &str) -> Option<&OpcodeDef> {
+        self.table.iter().flatten().find(|d| d.mnemonic == mnemonic)
+    }
+
+    // Like disasm::decode: None for cells that are not instructions here.
+    pub fn decode(&self, n: i64) -> Option<(&OpcodeDef, [Mode; MAX_PARAMS])> {
+        if n < 0 {
+            return None;
+        }
+        let def = self.get(n % 100)?;
+        let mut modes = [Mode::Position; MAX_PARAMS];
+        let mut digits = n / 100;
+        for m in modes.iter_mut().take(def.params.len()) {
+            *m = match digits % 10 {
+                0 => Mode::Position,
+                1 => Mode::Immediate,
+                2 => Mode::Relative,
+                _ => return None,
+            };
+            digits /= 10;
+        }
+        if digits != 0 {
+            return None;
+        }
+        Some((def, modes))
+    }
+
+    // step_program with this table.
+    pub fn step(&self, s: &mut ProgramState) -> bool {
+        let pc = s.pc;
+        let n = s.program[pc];
+        let def = match self.get(n % 100) {
+            Some(def) => def,
+            None => panic!("Illegal opcode {:?}", n % 100),
+        };
+
+        let mut modes = [Mode::Position; MAX_PARAMS];
+        let mut raw = [0; MAX_PARAMS];
+        let mut digits = n / 100;
+        for i in 0..def.params.len() {
+            modes[i] = digit_to_mode(digits % 10);
+            raw[i] = s.program[pc + 1 + i];
+            digits /= 10;
+        }
+
+        let mut args = Args {
+            state: s,
+            modes,
+            raw,
+        };
+        let effect = (def.handler)(&mut args);
+        let next = pc + 1 + def.params.len();
+
+        match effect {
+            Effect::Next => s.pc = next,
+            Effect::Jump(target) => s.pc = target,
+            Effect::Yield(state) => {
+                s.pc = next;
+                s.steps += 1;
+                s.return_state = state;
+                return true;
+            }
+            Effect::Wait => {
+                s.return_state = ReturnState::NeedMoreInput;
+                return true;
+            }
+            Effect::Halt => {
+                s.steps += 1;
+                s.return_state = ReturnState::Break;
+                return true;
+            }
+        }
+
+        s.steps += 1;
+        check_loop(s, pc)
+    }
+
+    pub fn run_program(&self, s: &mut ProgramState) {
+        while s.pc < s.program.len() {
+            if self.step(s) {
+                return;
+            }
+        }
+    }
+}
+
+// The parameters of the instruction n and their modes, the way machine s
+// will run it: by its registry if it has one, or else as a built-in. None
+// if it isn't an instruction.
+pub fn params_of(s: &ProgramState, n: i64) -> Option<(&[Param], [Mode; MAX_PARAMS])> {
+    use Param::*;
+
+    if let Some(registry) = &s.registry {
+        return registry
+            .decode(n)
+            .map(|(def, modes)| (def.params.as_slice(), modes));
+    }
+    let (op, modes) = decode(n)?;
+    let params: &[Param] = match (param_count(op), write_param(op)) {
+        (3, _) => &[Read, Read, Write],
+        (2, _) => &[Read, Read],
+        (1, Some(_)) => &[Write],
+        (1, None) => &[Read],
+        _ => &[],
+    };
+    Some((params, modes))
+}
+
+// The operands of the instruction at pc, checked against memory.
+pub struct Operands<'a> {
+    pub params: &'a [Param],
+    pub modes: [Mode; MAX_PARAMS],
+    // The operand cells as written in the program.
+    pub raw: [i64; MAX_PARAMS],
+    // The cell each operand reads or writes; None for immediate operands.
+    pub addrs: [Option<usize>; MAX_PARAMS],
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum OperandError {
+    // The cell at pc isn't an instruction.
+    BadInstruction(i64),
+    // The instruction runs past the end of memory, to this cell.
+    PastEnd(usize),
+    // Operand i writes through an immediate operand.
+    ImmediateWrite(usize),
+    // Operand i addresses this cell, which is negative or above the limit.
+    Address(usize, i64),
+    // A relative address that doesn't fit in an i64.
+    Overflow,
+}
+
+// Decodes the instruction at pc and checks everything about its operands
+// that would make step_program panic. Addresses must also be at most max.
+pub fn operands(s: &ProgramState, max: usize) -> Result<Operands<'_>, OperandError> {
+    let len = s.program.len();
+    if s.pc >= len {
+        return Err(OperandError::PastEnd(s.pc));
+    }
+    let n = s.program[s.pc];
+    let (params, modes) = params_of(s, n).ok_or(OperandError::BadInstruction(n))?;
+    if s.pc + params.len() >= len {
+        return Err(OperandError::PastEnd(s.pc + params.len()));
+    }
+
+    let max = max.min(len - 1);
+    let mut raw = [0; MAX_PARAMS];
+    let mut addrs = [None; MAX_PARAMS];
+    for (i, (param, mode)) in params.iter().zip(&modes).enumerate() {
+        raw[i] = s.program[s.pc + 1 + i];
+        let addr = match mode {
+            Mode::Position => raw[i],
+            Mode::Immediate if *param == Param::Write => {
+                return Err(OperandError::ImmediateWrite(i))
+            }
+            Mode::Immediate => continue,
+            Mode::Relative => s
+                .relative_base
+                .checked_add(raw[i])
+                .ok_or(OperandError::Overflow)?,
+        };
+        if addr < 0 || addr as usize > max {
+            return Err(OperandError::Address(i, addr));
+        }
+        addrs[i] = Some(addr as usize);
+    }
+    Ok(Operands {
+        params,
+        modes,
+        raw,
+        addrs,
+    })
+}
+
+// Whether the conditional jump at pc will jump, from its condition before it
+// runs. None if the instruction there isn't a conditional jump or can't run.
+pub fn jump_taken(s: &ProgramState) -> Option<bool> {
+    let ops = operands(s, usize::MAX).ok()?;
+    let cond = match ops.addrs[0] {
+        Some(addr) => s.program[addr],
+        None => ops.raw[0],
+    };
+    match decode(s.program[s.pc])?.0 {
+        Opcode::JumpIfTrue => Some(cond != 0),
+        Opcode::JumpIfFalse => Some(cond == 0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble_with;
+    use crate::disasm::disassemble_with;
+    use crate::until::{run_until, Stop, Until};
+    use crate::{parse_program, run_program};
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn run_to_end(registry: &Registry, program: Vec<i64>, inputs: Vec<i64>) -> ProgramState {
+        let mut s = ProgramState::new(program);
+        s.inputs = inputs;
+        while s.return_state == ReturnState::ProducedOutput {
+            registry.run_program(&mut s);
+        }
+        s
+    }
+
+    #[test]
+    fn test_run() {
+        let builtin = Registry::builtin();
+
+        // The table agrees with step_program on a real program.
+        let boost = parse_program(include_str!("../../09/input"));
+        let mut expected = ProgramState::new(boost.clone());
+        expected.inputs.push(1);
+        while expected.return_state == ReturnState::ProducedOutput {
+            run_program(&mut expected);
+        }
+        assert_eq!(run_to_end(&builtin, boost, vec![1]), expected);
+
+        let mut r = Registry::builtin();
+        // hcf <code>: halts with an exit code left in the outputs.
+        r.register(
+            OpcodeDef::new(98, "hcf", &[Param::Read], |a| {
+                let code = a.read(0);
+                a.state.outputs.push(code);
+                Effect::Halt
+            })
+            .with_flow(Flow::Halt),
+        )
+        .unwrap();
+        // rnd <dest>: xorshift, seeded in the closure.
+        let seed = AtomicU64::new(88172645463325252);
+        r.register(OpcodeDef::new(97, "rnd", &[Param::Write], move |a| {
+            let mut x = seed.load(Ordering::Relaxed);
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            seed.store(x, Ordering::Relaxed);
+            a.write(0, (x % 6) as i64 + 1);
+            Effect::Next
+        }))
+        .unwrap();
+        assert!(r
+            .register(OpcodeDef::new(96, "add", &[], |_| Effect::Next))
+            .is_err());
+
+        let program = assemble_with(
+            &r,
+            "
+            loop:
+                rnd [roll]
+                out [roll]
+                eq [roll], 6, [six]
+                jf [six], loop
+                hcf 3
+            roll: data 0
+            six: data 0
+            ",
+        )
+        .unwrap();
+        let s = run_to_end(&r, program.clone(), vec![]);
+        assert_eq!(s.return_state, ReturnState::Break);
+        assert_eq!(s.outputs[s.outputs.len() - 2..], [6, 3]);
+        assert!(s.outputs.iter().all(|v| (1..=6).contains(v)));
+
+        let listing: Vec<String> = disassemble_with(&r, &program, &BTreeSet::new())
+            .iter()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(listing[0], "rnd [13]");
+        assert_eq!(listing[4], "hcf 3");
+
+        // A machine with the table runs it through step_program too, so the
+        // other runners handle the new opcodes.
+        let mut s = ProgramState::new(program);
+        s.registry = Some(Arc::new(r));
+        let stop = run_until(&mut s, &Until::Halted);
+        assert_eq!(stop, Stop::Halted);
+        assert_eq!(*s.outputs.last().unwrap(), 3);
+    }
+}
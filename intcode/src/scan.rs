@@ -0,0 +1,168 @@
+// Value search over a live machine's memory, the way old cheat finders
+// worked: find every address holding a known value, let the program run,
+// narrow the list down by how the values moved, then pin what is left.
+
+use crate::memory::Memory;
+use crate::{step_program, ProgramState};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Condition {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(i64),
+}
+
+impl Condition {
+    fn holds(self, old: i64, new: i64) -> bool {
+        match self {
+            Condition::Changed => new != old,
+            Condition::Unchanged => new == old,
+            Condition::Increased => new > old,
+            Condition::Decreased => new < old,
+            Condition::Equals(n) => new == n,
+        }
+    }
+}
+
+// Candidate addresses with the value each held at the last scan.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scanner {
+    pub candidates: Vec<(usize, i64)>,
+}
+
+impl Scanner {
+    pub fn equals(memory: &Memory, n: i64) -> Scanner {
+        Scanner {
+            candidates: (0..memory.len())
+                .filter(|i| memory[*i] == n)
+                .map(|i| (i, n))
+                .collect(),
+        }
+    }
+
+    // Every address, for when the value itself is not known and only the way
+    // it changes is.
+    pub fn unknown(memory: &Memory) -> Scanner {
+        Scanner {
+            candidates: (0..memory.len()).map(|i| (i, memory[i])).collect(),
+        }
+    }
+
+    pub fn narrow(&mut self, memory: &Memory, condition: Condition) {
+        self.candidates = self
+            .candidates
+            .iter()
+            .map(|(addr, old)| (*addr, *old, memory[*addr]))
+            .filter(|(_, old, new)| condition.holds(*old, *new))
+            .map(|(addr, _, new)| (addr, new))
+            .collect();
+    }
+
+    pub fn addresses(&self) -> Vec<usize> {
+        self.candidates.iter().map(|c| c.0).collect()
+    }
+}
+
+// Addresses held at fixed values while the machine runs.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Pins {
+    pub values: BTreeMap<usize, i64>,
+}
+
+impl Pins {
+    pub fn new() -> Pins {
+        Pins::default()
+    }
+
+    pub fn pin(&mut self, addr: usize, value: i64) {
+        self.values.insert(addr, value);
+    }
+
+    pub fn unpin(&mut self, addr: usize) -> bool {
+        self.values.remove(&addr).is_some()
+    }
+
+    pub fn apply(&self, s: &mut ProgramState) {
+        for (addr, value) in &self.values {
+            if s.program[*addr] != *value {
+                s.write(*addr, *value);
+            }
+        }
+    }
+
+    // Drop-in replacement for run_program. Pins are put back after every
+    // instruction, so the program never reads anything else from them.
+    pub fn run_program(&self, s: &mut ProgramState) {
+        self.apply(s);
+        while s.pc < s.program.len() {
+            let yielded = step_program(s);
+            self.apply(s);
+            if yielded {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, ReturnState};
+
+    // Runs the day 13 arcade until it wants the joystick, returning the x of
+    // the last paddle tile drawn in this frame, if any.
+    fn frame(s: &mut ProgramState, pins: &Pins) -> Option<i64> {
+        let start = s.outputs.len();
+        s.return_state = ReturnState::ProducedOutput;
+        while s.return_state == ReturnState::ProducedOutput {
+            pins.run_program(s);
+        }
+        let drawn = &s.outputs[start..];
+        let mut paddle = None;
+        for tile in drawn.chunks(3) {
+            if tile.len() == 3 && tile[2] == 3 {
+                paddle = Some(tile[0]);
+            }
+        }
+        paddle
+    }
+
+    #[test]
+    fn test_run() {
+        let mut s = ProgramState::new(parse_program(include_str!("../../13/input.txt")));
+        s.write(0, 2);
+        let pins = Pins::new();
+
+        let x = frame(&mut s, &pins).unwrap();
+        let mut scan = Scanner::equals(&s.program, x);
+        assert!(scan.candidates.len() > 1);
+
+        s.inputs.push(1);
+        assert_eq!(frame(&mut s, &pins), Some(x + 1));
+        scan.narrow(&s.program, Condition::Increased);
+        scan.narrow(&s.program, Condition::Equals(x + 1));
+
+        s.inputs.push(0);
+        frame(&mut s, &pins);
+        scan.narrow(&s.program, Condition::Unchanged);
+
+        s.inputs.push(-1);
+        frame(&mut s, &pins);
+        scan.narrow(&s.program, Condition::Decreased);
+        assert_eq!(scan.candidates.len(), 1);
+        let paddle = scan.addresses()[0];
+        assert_eq!(s.program[paddle], x);
+
+        // Pinned, the paddle stays put whichever way the joystick goes.
+        let mut pins = Pins::new();
+        pins.pin(paddle, 5);
+        for joystick in &[1, 1, -1, 1] {
+            s.inputs.push(*joystick);
+            frame(&mut s, &pins);
+            assert_eq!(s.program[paddle], 5);
+        }
+    }
+}
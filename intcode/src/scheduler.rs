@@ -0,0 +1,282 @@
+// Runs many machines on one thread by time slicing: each turn one machine
+// runs for up to `slice` instructions, then its new outputs are delivered
+// to the machines connected to it. A machine waiting for input it doesn't
+// have is parked until some arrives, and a halted one is dropped from the
+// rotation.
+//
+// Unlike Network nothing depends on the OS scheduler, so the same machines
+// always run in the same order and take the same number of steps. Time is
+// the clock: the total number of instructions run so far.
+
+use crate::{step_program, ProgramState, ReturnState};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Order {
+    RoundRobin,
+    // The highest priority machine that is ready runs; equal priorities take
+    // turns.
+    Priority,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    Ready,
+    Parked,
+    // Halted or stuck in a loop.
+    Stopped,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Stats {
+    pub steps: u64,
+    pub slices: u64,
+    // Clock time spent parked, up to the last time input arrived.
+    pub blocked: u64,
+    // When the machine was last parked, if it still is.
+    pub parked_since: Option<u64>,
+}
+
+struct Machine {
+    state: ProgramState,
+    priority: i64,
+    status: Status,
+    stats: Stats,
+    outs: Vec<usize>,
+    // Outputs already delivered to outs.
+    sent: usize,
+}
+
+pub struct Scheduler {
+    pub order: Order,
+    pub slice: u64,
+    pub clock: u64,
+    machines: Vec<Machine>,
+    // Where round robin looks for the next machine.
+    next: usize,
+}
+
+impl Scheduler {
+    pub fn new(order: Order) -> Scheduler {
+        Scheduler {
+            order,
+            slice: 1000,
+            clock: 0,
+            machines: Vec::new(),
+            next: 0,
+        }
+    }
+
+    pub fn add(&mut self, state: ProgramState) -> usize {
+        self.add_with_priority(state, 0)
+    }
+
+    pub fn add_with_priority(&mut self, state: ProgramState, priority: i64) -> usize {
+        let sent = state.outputs.len();
+        self.machines.push(Machine {
+            state,
+            priority,
+            status: Status::Ready,
+            stats: Stats::default(),
+            outs: Vec::new(),
+            sent,
+        });
+        self.machines.len() - 1
+    }
+
+    // Every output of from also goes to the input of to.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.machines[from].outs.push(to);
+    }
+
+    pub fn push_input(&mut self, to: usize, value: i64) {
+        self.machines[to].state.inputs.push(value);
+        self.unpark(to);
+    }
+
+    fn unpark(&mut self, id: usize) {
+        let m = &mut self.machines[id];
+        if let Some(since) = m.stats.parked_since.take() {
+            m.stats.blocked += self.clock - since;
+            m.status = Status::Ready;
+        }
+    }
+
+    pub fn state(&self, id: usize) -> &ProgramState {
+        &self.machines[id].state
+    }
+
+    // For poking at a machine between runs. A parked machine is only
+    // retried after push_input.
+    pub fn state_mut(&mut self, id: usize) -> &mut ProgramState {
+        &mut self.machines[id].state
+    }
+
+    pub fn status(&self, id: usize) -> Status {
+        self.machines[id].status
+    }
+
+    pub fn stats(&self, id: usize) -> &Stats {
+        &self.machines[id].stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    pub fn into_states(self) -> Vec<ProgramState> {
+        self.machines.into_iter().map(|m| m.state).collect()
+    }
+
+    fn pick(&self) -> Option<usize> {
+        let n = self.machines.len();
+        let ready = (0..n)
+            .map(|i| (self.next + i) % n)
+            .filter(|i| self.machines[*i].status == Status::Ready);
+        match self.order {
+            Order::RoundRobin => ready.take(1).next(),
+            // max_by_key keeps the last of equals, so go by the earliest.
+            Order::Priority => ready
+                .enumerate()
+                .max_by_key(|(pos, i)| (self.machines[*i].priority, std::cmp::Reverse(*pos)))
+                .map(|(_, i)| i),
+        }
+    }
+
+    // Runs one slice of the next ready machine and returns which it was, or
+    // None if every machine is parked or stopped.
+    pub fn run_slice(&mut self) -> Option<usize> {
+        let id = self.pick()?;
+        self.next = id + 1;
+
+        let slice = self.slice;
+        let m = &mut self.machines[id];
+        let s = &mut m.state;
+        let start = s.steps;
+        s.return_state = ReturnState::ProducedOutput;
+        while s.steps - start < slice && s.pc < s.program.len() {
+            if step_program(s) && s.return_state != ReturnState::ProducedOutput {
+                break;
+            }
+        }
+        let ran = s.steps - start;
+        m.stats.steps += ran;
+        m.stats.slices += 1;
+        self.clock += ran;
+
+        m.status = match s.return_state {
+            ReturnState::NeedMoreInput => Status::Parked,
+            ReturnState::Break | ReturnState::InfiniteLoop { .. } => Status::Stopped,
+            _ if s.pc >= s.program.len() => Status::Stopped,
+            _ => Status::Ready,
+        };
+        if m.status == Status::Parked {
+            m.stats.parked_since = Some(self.clock);
+        }
+
+        let outputs = m.state.outputs[m.sent..].to_vec();
+        m.sent = m.state.outputs.len();
+        for to in m.outs.clone() {
+            for v in &outputs {
+                self.push_input(to, *v);
+            }
+        }
+        Some(id)
+    }
+
+    // Runs slices until no machine is ready or the clock reaches max_clock.
+    // Returns true if it stopped because everything was parked or stopped.
+    pub fn run_until(&mut self, max_clock: u64) -> bool {
+        while self.clock < max_clock {
+            if self.run_slice().is_none() {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn run(&mut self) {
+        self.run_until(u64::MAX);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+    use std::sync::Arc;
+
+    fn amplifiers(order: Order, slice: u64) -> Scheduler {
+        let amp = parse_program(include_str!("../../07/input.txt"));
+        let mut sched = Scheduler::new(order);
+        sched.slice = slice;
+        for phase in [9, 8, 7, 6, 5] {
+            let mut s = ProgramState::new(amp.clone());
+            s.inputs.push(phase);
+            sched.add(s);
+        }
+        for i in 0..5 {
+            sched.connect(i, (i + 1) % 5);
+        }
+        sched.push_input(0, 0);
+        sched
+    }
+
+    #[test]
+    fn test_run() {
+        // Day 7's feedback loop gives the same answer however it's sliced,
+        // and the same accounting every time.
+        let mut sched = amplifiers(Order::RoundRobin, 1000);
+        sched.run();
+        let answer = *sched.state(4).outputs.last().unwrap();
+        assert!((0..5).all(|i| sched.status(i) == Status::Stopped));
+        assert!(sched.stats(1).blocked > 0);
+        assert_eq!(
+            sched.clock,
+            (0..5).map(|i| sched.stats(i).steps).sum::<u64>()
+        );
+        let mut tiny = amplifiers(Order::Priority, 3);
+        tiny.run();
+        assert_eq!(*tiny.state(4).outputs.last().unwrap(), answer);
+        assert_eq!(tiny.clock, sched.clock);
+        let mut again = amplifiers(Order::RoundRobin, 1000);
+        again.run();
+        assert!((0..5).all(|i| again.stats(i) == sched.stats(i)));
+
+        // Thousands of counters: round robin shares the clock evenly.
+        let counter = Arc::new(parse_program("1001,20,1,20,1105,1,0"));
+        let mut sched = Scheduler::new(Order::RoundRobin);
+        sched.slice = 10;
+        for _ in 0..2000 {
+            sched.add(ProgramState::from_image(counter.clone()));
+        }
+        assert!(!sched.run_until(200000));
+        assert!((0..2000).all(|i| sched.stats(i).steps == 100));
+
+        // Strict priority starves the others while the first can run.
+        let mut sched = Scheduler::new(Order::Priority);
+        let low = sched.add(ProgramState::from_image(counter));
+        let high =
+            sched.add_with_priority(ProgramState::new(parse_program("3,20,4,20,1105,1,0")), 1);
+        sched.connect(high, low);
+        sched.push_input(high, 42);
+        assert_eq!(sched.run_slice(), Some(high));
+        assert_eq!(sched.status(high), Status::Parked);
+        assert_eq!(sched.state(low).inputs, [42]);
+        assert!(!sched.run_until(5000));
+        assert_eq!(
+            sched.stats(low).steps,
+            sched.clock - sched.stats(high).steps
+        );
+        sched.push_input(high, 7);
+        assert_eq!(sched.run_slice(), Some(high));
+        assert_eq!(
+            sched.stats(high).blocked,
+            sched.clock - sched.stats(high).steps
+        );
+        assert_eq!(sched.stats(high).parked_since, Some(sched.clock));
+    }
+}
@@ -0,0 +1,44 @@
+// The --seed option of the binaries that play or explore at random. A run
+// started with the same seed makes the same moves, so it can be repeated
+// and tested.
+
+// Removes "--seed <n>" from args and returns n, or None if the option isn't
+// there. An error if the value is missing or not a number; args is left
+// alone then.
+pub fn take_seed(args: &mut Vec<String>) -> Result<Option<u64>, String> {
+    let i = match args.iter().position(|a| a == "--seed") {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    let seed = match args.get(i + 1) {
+        Some(v) => v.parse::<u64>().map_err(|_| format!("bad seed {}", v))?,
+        None => return Err("--seed needs a value".to_owned()),
+    };
+    args.drain(i..i + 2);
+    Ok(Some(seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(|a| a.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_run() {
+        let mut a = args("game --seed 42 input.txt");
+        assert_eq!(take_seed(&mut a), Ok(Some(42)));
+        assert_eq!(a, args("game input.txt"));
+
+        let mut a = args("game input.txt");
+        assert_eq!(take_seed(&mut a), Ok(None));
+        assert_eq!(a, args("game input.txt"));
+
+        let mut a = args("game input.txt --seed");
+        assert!(take_seed(&mut a).is_err());
+        assert_eq!(a, args("game input.txt --seed"));
+        assert!(take_seed(&mut args("game --seed x input.txt")).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+// Input sessions: every input a machine consumed, with the step it was
+// consumed at, plus the outputs it produced. Replaying a session feeds the
+// same inputs to a fresh machine and checks that it consumes them at the
+// same steps and produces the same outputs.
+//
+// Session files are plain text:
+//
+//   intcode-session
+//   input <step> <value>
+//   output <value>
+//
+// A host that runs more than one machine, like 07's amplifiers or 19's
+// drone probes, saves them all to one file with a "machine" line between
+// them.
+//
+// The hosts take a --session <file> option to record what they ran; 13/a
+// takes the file as its second argument.
+
+use crate::{run_program, ProgramState, ReturnState};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Session {
+    pub inputs: Vec<(u64, i64)>,
+    pub outputs: Vec<i64>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Mismatch {
+    InputStep {
+        index: usize,
+        expected: u64,
+        actual: Option<u64>,
+    },
+    Output {
+        index: usize,
+        expected: Option<i64>,
+        actual: Option<i64>,
+    },
+}
+
+impl Session {
+    // The session that led to s. Works on forks and restored save-states
+    // too, since the history travels with the machine.
+    pub fn from_state(s: &ProgramState) -> Session {
+        Session {
+            inputs: s
+                .input_steps
+                .iter()
+                .cloned()
+                .zip(s.inputs.iter().cloned())
+                .collect(),
+            outputs: s.outputs.clone(),
+        }
+    }
+
+    pub fn replay(&self, program: Vec<i64>) -> Result<ProgramState, Mismatch> {
+        let mut s = ProgramState::new(program);
+        s.inputs = self.inputs.iter().map(|i| i.1).collect();
+
+        while s.return_state == ReturnState::ProducedOutput {
+            run_program(&mut s);
+        }
+
+        for (index, (expected, _)) in self.inputs.iter().enumerate() {
+            let actual = s.input_steps.get(index).cloned();
+            if actual != Some(*expected) {
+                return Err(Mismatch::InputStep {
+                    index,
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+
+        for index in 0..self.outputs.len().max(s.outputs.len()) {
+            let expected = self.outputs.get(index).cloned();
+            let actual = s.outputs.get(index).cloned();
+            if expected != actual {
+                return Err(Mismatch::Output {
+                    index,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        Ok(s)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        Session::save_all(std::slice::from_ref(self), path)
+    }
+
+    pub fn save_all<P: AsRef<Path>>(sessions: &[Session], path: P) -> io::Result<()> {
+        let mut res = "intcode-session\n".to_owned();
+        for (i, session) in sessions.iter().enumerate() {
+            if i > 0 {
+                res.push_str("machine\n");
+            }
+            for (step, value) in &session.inputs {
+                let _ = writeln!(res, "input {} {}", step, value);
+            }
+            for value in &session.outputs {
+                let _ = writeln!(res, "output {}", value);
+            }
+        }
+        fs::write(path, res)
+    }
+
+    // A file with one machine in it; see load_all for more.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Session> {
+        let mut sessions = Session::load_all(path)?;
+        if sessions.len() != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("session file holds {} machines", sessions.len()),
+            ));
+        }
+        Ok(sessions.remove(0))
+    }
+
+    pub fn load_all<P: AsRef<Path>>(path: P) -> io::Result<Vec<Session>> {
+        let text = fs::read_to_string(path)?;
+        let bad = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bad session line: {}", line),
+            )
+        };
+
+        let mut lines = text.lines();
+        if lines.next() != Some("intcode-session") {
+            return Err(bad("missing intcode-session header"));
+        }
+
+        let mut sessions = vec![Session::default()];
+        for line in lines {
+            let session = sessions.last_mut().unwrap();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                ["input", step, value] => session.inputs.push((
+                    step.parse().map_err(|_| bad(line))?,
+                    value.parse().map_err(|_| bad(line))?,
+                )),
+                ["output", value] => session.outputs.push(value.parse().map_err(|_| bad(line))?),
+                ["machine"] => sessions.push(Session::default()),
+                [] => {}
+                _ => return Err(bad(line)),
+            }
+        }
+        Ok(sessions)
+    }
+}
+
+// Removes "--session <file>" from args and returns the file, or None if the
+// option isn't there. An error if the file is missing; args is left alone
+// then.
+pub fn take_session_file(args: &mut Vec<String>) -> Result<Option<String>, String> {
+    let i = match args.iter().position(|a| a == "--session") {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    let file = match args.get(i + 1) {
+        Some(f) => f.clone(),
+        None => return Err("--session needs a file".to_owned()),
+    };
+    args.drain(i..i + 2);
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_program;
+
+    #[test]
+    fn test_run() {
+        // Echoes inputs doubled until it reads a 0.
+        let program = parse_program("3,20,1006,20,14,1002,20,2,21,4,21,1105,1,0,99");
+
+        let mut s = ProgramState::new(program.clone());
+        s.inputs = vec![3];
+        run_program(&mut s);
+        let saved = s.fork();
+        s.inputs.push(5);
+        run_program(&mut s);
+
+        // Backtrack to the save-state and take another path.
+        let mut s = saved;
+        s.inputs.push(7);
+        run_program(&mut s);
+        s.inputs.push(0);
+        while s.return_state == ReturnState::ProducedOutput {
+            run_program(&mut s);
+        }
+        assert_eq!(s.return_state, ReturnState::Break);
+
+        let session = Session::from_state(&s);
+        assert_eq!(
+            session.inputs.iter().map(|i| i.1).collect::<Vec<_>>(),
+            [3, 7, 0]
+        );
+        assert_eq!(session.outputs, [6, 14]);
+
+        let path = std::env::temp_dir().join(format!("intcode-session-{}", std::process::id()));
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, session);
+
+        session.save(&path).unwrap();
+        assert_eq!(
+            Session::load_all(&path).unwrap(),
+            std::slice::from_ref(&session)
+        );
+        let two = [session.clone(), Session::default()];
+        Session::save_all(&two, &path).unwrap();
+        assert_eq!(Session::load_all(&path).unwrap(), two);
+        assert!(Session::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let mut args: Vec<String> = vec!["day".into(), "--session".into(), "s.txt".into()];
+        assert_eq!(take_session_file(&mut args), Ok(Some("s.txt".to_owned())));
+        assert_eq!(args, ["day"]);
+        assert_eq!(take_session_file(&mut args), Ok(None));
+        args.push("--session".into());
+        assert!(take_session_file(&mut args).is_err());
+
+        let replayed = loaded.replay(program.clone()).unwrap();
+        assert_eq!(replayed.outputs, s.outputs);
+
+        let mut tampered = loaded.clone();
+        tampered.outputs[1] = 15;
+        assert_eq!(
+            tampered.replay(program.clone()),
+            Err(Mismatch::Output {
+                index: 1,
+                expected: Some(15),
+                actual: Some(14)
+            })
+        );
+
+        let mut tampered = loaded;
+        tampered.inputs[1].0 += 1;
+        match tampered.replay(program) {
+            Err(Mismatch::InputStep { index: 1, .. }) => {}
+            other => panic!("unexpected replay result {:?}", other),
+        }
+    }
+}
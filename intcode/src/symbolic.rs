@@ -0,0 +1,712 @@
+// Symbolic execution over the intcode instruction set.
+//
+// Chosen memory cells and the values read by Input instructions become
+// symbols with a bounded domain. Arithmetic on symbols builds expressions,
+// and execution forks at comparisons and conditional jumps whose operands
+// are symbolic, each side recording the outcome as a path constraint.
+// Addresses, jump targets and the relative base must stay concrete; a path
+// that would need a symbolic one is ended with SymbolicAddress.
+//
+// Constraints are solved by a depth first search over the symbol domains.
+// Equalities that are linear in the last unassigned symbol are solved
+// directly instead of enumerated, so "noun * k + verb == x" only searches
+// over noun.
+
+use crate::disasm::decode;
+use crate::{Mode, Opcode};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+type Overlay = Rc<BTreeMap<usize, Rc<Expr>>>;
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Const(i64),
+    Sym(usize),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    Lt(Rc<Expr>, Rc<Expr>),
+    Eq(Rc<Expr>, Rc<Expr>),
+    // Read through a symbolic address, against the memory as it was when
+    // the read happened.
+    Load(Rc<Expr>, Overlay, Rc<Vec<i64>>),
+}
+
+impl Expr {
+    pub fn constant(&self) -> Option<i64> {
+        match self {
+            Expr::Const(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn add(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Rc::new(Expr::Const(x.wrapping_add(y))),
+            (Some(0), _) => b,
+            (_, Some(0)) => a,
+            _ => Rc::new(Expr::Add(a, b)),
+        }
+    }
+
+    fn mul(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.constant(), b.constant()) {
+            (Some(x), Some(y)) => Rc::new(Expr::Const(x.wrapping_mul(y))),
+            (Some(0), _) | (_, Some(0)) => Rc::new(Expr::Const(0)),
+            (Some(1), _) => b,
+            (_, Some(1)) => a,
+            _ => Rc::new(Expr::Mul(a, b)),
+        }
+    }
+
+    fn load(addr: Rc<Expr>, overlay: &Overlay, base: &Rc<Vec<i64>>) -> Rc<Expr> {
+        Rc::new(Expr::Load(addr, overlay.clone(), base.clone()))
+    }
+
+    // Evaluates under a model; None if a symbol is unassigned or a load
+    // falls outside memory.
+    pub fn eval(&self, model: &BTreeMap<usize, i64>) -> Option<i64> {
+        Some(match self {
+            Expr::Const(v) => *v,
+            Expr::Sym(id) => *model.get(id)?,
+            Expr::Add(a, b) => a.eval(model)?.wrapping_add(b.eval(model)?),
+            Expr::Mul(a, b) => a.eval(model)?.wrapping_mul(b.eval(model)?),
+            Expr::Lt(a, b) => (a.eval(model)? < b.eval(model)?) as i64,
+            Expr::Eq(a, b) => (a.eval(model)? == b.eval(model)?) as i64,
+            Expr::Load(addr, overlay, base) => {
+                let addr = addr.eval(model)?;
+                if addr < 0 || addr as usize >= base.len() {
+                    return None;
+                }
+                match overlay.get(&(addr as usize)) {
+                    Some(e) => e.eval(model)?,
+                    None => base[addr as usize],
+                }
+            }
+        })
+    }
+
+    fn collect_symbols(&self, out: &mut Vec<usize>) {
+        match self {
+            Expr::Const(_) => {}
+            Expr::Sym(id) => {
+                if !out.contains(id) {
+                    out.push(*id);
+                }
+            }
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::Lt(a, b) | Expr::Eq(a, b) => {
+                a.collect_symbols(out);
+                b.collect_symbols(out);
+            }
+            Expr::Load(addr, overlay, _) => {
+                addr.collect_symbols(out);
+                for e in overlay.values() {
+                    e.collect_symbols(out);
+                }
+            }
+        }
+    }
+
+    // Linear form (coefficients per unassigned symbol, constant) if the
+    // expression is linear once the assigned symbols are substituted.
+    fn linearize(&self, model: &BTreeMap<usize, i64>) -> Option<(BTreeMap<usize, i64>, i64)> {
+        match self {
+            Expr::Const(v) => Some((BTreeMap::new(), *v)),
+            Expr::Sym(id) => match model.get(id) {
+                Some(v) => Some((BTreeMap::new(), *v)),
+                None => {
+                    let mut coefs = BTreeMap::new();
+                    coefs.insert(*id, 1);
+                    Some((coefs, 0))
+                }
+            },
+            Expr::Add(a, b) => {
+                let (mut coefs, c1) = a.linearize(model)?;
+                let (coefs2, c2) = b.linearize(model)?;
+                for (id, k) in coefs2 {
+                    let e = coefs.entry(id).or_insert(0);
+                    *e = e.checked_add(k)?;
+                }
+                Some((coefs, c1.checked_add(c2)?))
+            }
+            Expr::Mul(a, b) => {
+                let (coefs1, c1) = a.linearize(model)?;
+                let (coefs2, c2) = b.linearize(model)?;
+                let (coefs, c, k) = if coefs1.is_empty() {
+                    (coefs2, c2, c1)
+                } else if coefs2.is_empty() {
+                    (coefs1, c1, c2)
+                } else {
+                    return None;
+                };
+                let mut scaled = BTreeMap::new();
+                for (id, v) in coefs {
+                    scaled.insert(id, v.checked_mul(k)?);
+                }
+                Some((scaled, c.checked_mul(k)?))
+            }
+            _ => Some((BTreeMap::new(), self.eval(model)?)),
+        }
+    }
+}
+
+// (expr != 0) == holds
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub expr: Rc<Expr>,
+    pub holds: bool,
+}
+
+impl Constraint {
+    pub fn equals(expr: Rc<Expr>, value: i64) -> Constraint {
+        Constraint {
+            expr: Rc::new(Expr::Eq(expr, Rc::new(Expr::Const(value)))),
+            holds: true,
+        }
+    }
+
+    fn check(&self, model: &BTreeMap<usize, i64>) -> Option<bool> {
+        Some((self.expr.eval(model)? != 0) == self.holds)
+    }
+
+    // The expression that must be zero, if this is an equality.
+    fn zero_expr(&self) -> Option<Rc<Expr>> {
+        match (&*self.expr, self.holds) {
+            (Expr::Eq(a, b), true) => Some(Expr::add(
+                a.clone(),
+                Expr::mul(b.clone(), Rc::new(Expr::Const(-1))),
+            )),
+            (_, false) => Some(self.expr.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub lo: i64,
+    pub hi: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    pub values: BTreeMap<usize, i64>,
+}
+
+impl Model {
+    pub fn value(&self, sym: usize) -> i64 {
+        self.values[&sym]
+    }
+}
+
+pub fn solve(symbols: &[Symbol], constraints: &[Constraint]) -> Option<Model> {
+    let mut order = Vec::new();
+    for c in constraints {
+        c.expr.collect_symbols(&mut order);
+    }
+    order.sort_unstable();
+
+    let mut model = BTreeMap::new();
+    if search(symbols, constraints, &order, &mut model) {
+        // Symbols no constraint mentions can take any value in their domain.
+        for (id, sym) in symbols.iter().enumerate() {
+            model.entry(id).or_insert(sym.lo);
+        }
+        Some(Model { values: model })
+    } else {
+        None
+    }
+}
+
+fn search(
+    symbols: &[Symbol],
+    constraints: &[Constraint],
+    order: &[usize],
+    model: &mut BTreeMap<usize, i64>,
+) -> bool {
+    for c in constraints {
+        if c.check(model) == Some(false) {
+            return false;
+        }
+    }
+
+    let (id, rest) = match order.split_first() {
+        Some(split) => split,
+        None => return constraints.iter().all(|c| c.check(model) == Some(true)),
+    };
+    let sym = &symbols[*id];
+
+    if rest.is_empty() {
+        for c in constraints {
+            if let Some(candidates) = solve_linear(c, *id, model) {
+                for v in candidates
+                    .into_iter()
+                    .filter(|v| *v >= sym.lo && *v <= sym.hi)
+                {
+                    model.insert(*id, v);
+                    if search(symbols, constraints, rest, model) {
+                        return true;
+                    }
+                    model.remove(id);
+                }
+                return false;
+            }
+        }
+    }
+
+    for v in sym.lo..=sym.hi {
+        model.insert(*id, v);
+        if search(symbols, constraints, rest, model) {
+            return true;
+        }
+    }
+    model.remove(id);
+
+    false
+}
+
+// For an equality k * x + c == 0 in the single unassigned symbol x, the
+// candidates for x (none or one). None if the constraint isn't of that form
+// or holds whatever x is.
+fn solve_linear(c: &Constraint, id: usize, model: &BTreeMap<usize, i64>) -> Option<Vec<i64>> {
+    let (coefs, constant) = c.zero_expr()?.linearize(model)?;
+    if coefs.len() != 1 {
+        return None;
+    }
+    let k = *coefs.get(&id)?;
+    if k == 0 {
+        return if constant == 0 { None } else { Some(vec![]) };
+    }
+    // In i128, so that i64::MIN / -1 can't overflow.
+    let (k, constant) = (k as i128, constant as i128);
+    if constant % k != 0 {
+        return Some(vec![]);
+    }
+    Some(i64::try_from(-constant / k).into_iter().collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathEnd {
+    Halted,
+    NeedMoreInput,
+    StepLimit,
+    SymbolicAddress(usize),
+    BadInstruction(usize),
+    BadAddress(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub pc: usize,
+    pub relative_base: i64,
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Rc<Expr>>,
+    pub inputs_used: usize,
+    pub steps: u64,
+    pub end: PathEnd,
+    memory: Overlay,
+    base: Rc<Vec<i64>>,
+}
+
+impl Path {
+    pub fn memory(&self, addr: usize) -> Rc<Expr> {
+        match self.memory.get(&addr) {
+            Some(e) => e.clone(),
+            None => Rc::new(Expr::Const(self.base[addr])),
+        }
+    }
+}
+
+pub struct SymbolicExecutor {
+    base: Rc<Vec<i64>>,
+    symbols: Vec<Symbol>,
+    cells: Vec<(usize, usize)>,
+    inputs: Vec<usize>,
+    pub max_paths: usize,
+    pub max_steps: u64,
+}
+
+impl SymbolicExecutor {
+    pub fn new(program: &[i64]) -> SymbolicExecutor {
+        SymbolicExecutor {
+            base: Rc::new(program.to_vec()),
+            symbols: Vec::new(),
+            cells: Vec::new(),
+            inputs: Vec::new(),
+            max_paths: 10000,
+            max_steps: 1000000,
+        }
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // Makes the initial value of a memory cell a symbol in lo..=hi.
+    pub fn symbolic_memory(&mut self, addr: usize, lo: i64, hi: i64) -> usize {
+        self.symbols.push(Symbol {
+            name: format!("mem[{}]", addr),
+            lo,
+            hi,
+        });
+        self.cells.push((addr, self.symbols.len() - 1));
+        self.symbols.len() - 1
+    }
+
+    // Makes the next value read by an Input instruction a symbol in lo..=hi.
+    // Paths that read more inputs than were declared end in NeedMoreInput.
+    pub fn symbolic_input(&mut self, lo: i64, hi: i64) -> usize {
+        self.symbols.push(Symbol {
+            name: format!("input{}", self.inputs.len()),
+            lo,
+            hi,
+        });
+        self.inputs.push(self.symbols.len() - 1);
+        self.symbols.len() - 1
+    }
+
+    pub fn explore(&self) -> Vec<Path> {
+        let mut memory = BTreeMap::new();
+        for (addr, id) in &self.cells {
+            memory.insert(*addr, Rc::new(Expr::Sym(*id)));
+        }
+
+        let mut pending = vec![Path {
+            pc: 0,
+            relative_base: 0,
+            constraints: Vec::new(),
+            outputs: Vec::new(),
+            inputs_used: 0,
+            steps: 0,
+            end: PathEnd::StepLimit,
+            memory: Rc::new(memory),
+            base: self.base.clone(),
+        }];
+        let mut done = Vec::new();
+
+        while let Some(mut path) = pending.pop() {
+            if done.len() >= self.max_paths {
+                break;
+            }
+            let fork = self.run_path(&mut path);
+            for path in std::iter::once(path).chain(fork) {
+                if !self.feasible(&path.constraints) {
+                    continue;
+                }
+                if path.end != PathEnd::StepLimit || path.steps >= self.max_steps {
+                    done.push(path);
+                } else {
+                    pending.push(path);
+                }
+            }
+        }
+
+        done
+    }
+
+    // Finds symbol values for which some halting path leaves value at addr.
+    pub fn solve_memory(&self, addr: usize, value: i64) -> Option<Model> {
+        self.solve_paths(|path| Some(Constraint::equals(path.memory(addr), value)))
+    }
+
+    // Finds symbol values for which output number index equals value.
+    pub fn solve_output(&self, index: usize, value: i64) -> Option<Model> {
+        self.solve_paths(|path| {
+            let out = path.outputs.get(index)?;
+            Some(Constraint::equals(out.clone(), value))
+        })
+    }
+
+    fn solve_paths<F: Fn(&Path) -> Option<Constraint>>(&self, goal: F) -> Option<Model> {
+        for path in self.explore() {
+            if path.end != PathEnd::Halted && path.end != PathEnd::NeedMoreInput {
+                continue;
+            }
+            let goal = match goal(&path) {
+                Some(goal) => goal,
+                None => continue,
+            };
+            let mut constraints = path.constraints.clone();
+            constraints.push(goal);
+            if let Some(model) = solve(&self.symbols, &constraints) {
+                return Some(model);
+            }
+        }
+        None
+    }
+
+    fn feasible(&self, constraints: &[Constraint]) -> bool {
+        solve(&self.symbols, constraints).is_some()
+    }
+
+    // Runs until the path ends or forks. A fork is returned as a second
+    // path, both with end still at StepLimit so they get resumed.
+    fn run_path(&self, p: &mut Path) -> Option<Path> {
+        while p.steps < self.max_steps {
+            let opcode = match self.read(p, p.pc).constant() {
+                Some(op) => op,
+                None => {
+                    p.end = PathEnd::SymbolicAddress(p.pc);
+                    return None;
+                }
+            };
+            let (op, [mode1, mode2, mode3]) = match decode(opcode) {
+                Some(decoded) => decoded,
+                None => {
+                    p.end = PathEnd::BadInstruction(p.pc);
+                    return None;
+                }
+            };
+
+            match op {
+                Opcode::Addition | Opcode::Multiplication => {
+                    let a = self.param(p, 1, mode1)?;
+                    let b = self.param(p, 2, mode2)?;
+                    let c = if op == Opcode::Addition {
+                        Expr::add(a, b)
+                    } else {
+                        Expr::mul(a, b)
+                    };
+                    self.write(p, 3, mode3, c)?;
+                    p.pc += 4;
+                }
+                Opcode::LessThan | Opcode::Equals => {
+                    let a = self.param(p, 1, mode1)?;
+                    let b = self.param(p, 2, mode2)?;
+                    let cmp = if op == Opcode::LessThan {
+                        Expr::Lt(a, b)
+                    } else {
+                        Expr::Eq(a, b)
+                    };
+                    let no_model = BTreeMap::new();
+                    match cmp.eval(&no_model) {
+                        Some(v) => {
+                            self.write(p, 3, mode3, Rc::new(Expr::Const(v)))?;
+                            p.pc += 4;
+                        }
+                        None => {
+                            let cmp = Rc::new(cmp);
+                            let mut other = p.clone();
+                            p.steps += 1;
+                            other.steps += 1;
+                            // A bad write address ends both sides, not
+                            // just this one.
+                            self.fork(p, &cmp, true);
+                            if self.write(p, 3, mode3, Rc::new(Expr::Const(1))).is_some() {
+                                p.pc += 4;
+                            }
+                            self.fork(&mut other, &cmp, false);
+                            if self
+                                .write(&mut other, 3, mode3, Rc::new(Expr::Const(0)))
+                                .is_some()
+                            {
+                                other.pc += 4;
+                            }
+                            return Some(other);
+                        }
+                    }
+                }
+                Opcode::Input => {
+                    if p.inputs_used >= self.inputs.len() {
+                        p.end = PathEnd::NeedMoreInput;
+                        return None;
+                    }
+                    let sym = Rc::new(Expr::Sym(self.inputs[p.inputs_used]));
+                    self.write(p, 1, mode1, sym)?;
+                    p.inputs_used += 1;
+                    p.pc += 2;
+                }
+                Opcode::Output => {
+                    let a = self.param(p, 1, mode1)?;
+                    p.outputs.push(a);
+                    p.pc += 2;
+                }
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                    let cond = self.param(p, 1, mode1)?;
+                    let target = self.param(p, 2, mode2)?;
+                    let target = match target.constant() {
+                        Some(t) if t >= 0 => t as usize,
+                        _ => {
+                            p.end = PathEnd::SymbolicAddress(p.pc);
+                            return None;
+                        }
+                    };
+                    let jump_when = op == Opcode::JumpIfTrue;
+                    match cond.constant() {
+                        Some(v) => {
+                            if (v != 0) == jump_when {
+                                p.pc = target;
+                            } else {
+                                p.pc += 3;
+                            }
+                        }
+                        None => {
+                            let mut other = p.clone();
+                            p.steps += 1;
+                            other.steps += 1;
+                            self.fork(p, &cond, jump_when);
+                            p.pc = target;
+                            self.fork(&mut other, &cond, !jump_when);
+                            other.pc += 3;
+                            return Some(other);
+                        }
+                    }
+                }
+                Opcode::AdjustRelativeBase => match self.param(p, 1, mode1)?.constant() {
+                    Some(v) => {
+                        p.relative_base += v;
+                        p.pc += 2;
+                    }
+                    None => {
+                        p.end = PathEnd::SymbolicAddress(p.pc);
+                        return None;
+                    }
+                },
+                Opcode::Break => {
+                    p.end = PathEnd::Halted;
+                    return None;
+                }
+            }
+
+            p.steps += 1;
+        }
+
+        None
+    }
+
+    fn fork(&self, p: &mut Path, cond: &Rc<Expr>, holds: bool) {
+        p.constraints.push(Constraint {
+            expr: cond.clone(),
+            holds,
+        });
+    }
+
+    fn read(&self, p: &Path, addr: usize) -> Rc<Expr> {
+        if addr >= self.base.len() {
+            return Rc::new(Expr::Const(0));
+        }
+        p.memory(addr)
+    }
+
+    // Value of operand n. A position or relative operand that is itself
+    // symbolic turns into a load through a symbolic address.
+    fn param(&self, p: &mut Path, n: usize, mode: Mode) -> Option<Rc<Expr>> {
+        let val = self.read(p, p.pc + n);
+        let addr = match mode {
+            Mode::Immediate => return Some(val),
+            Mode::Position => val,
+            Mode::Relative => Expr::add(val, Rc::new(Expr::Const(p.relative_base))),
+        };
+        match addr.constant() {
+            Some(a) if a >= 0 && (a as usize) < self.base.len() => Some(self.read(p, a as usize)),
+            Some(a) => {
+                p.end = PathEnd::BadAddress(a as usize);
+                None
+            }
+            None => Some(Expr::load(addr, &p.memory, &self.base)),
+        }
+    }
+
+    fn write(&self, p: &mut Path, n: usize, mode: Mode, value: Rc<Expr>) -> Option<()> {
+        let val = self.read(p, p.pc + n).constant();
+        let addr = match (mode, val) {
+            (Mode::Position, Some(v)) => v,
+            (Mode::Relative, Some(v)) => v + p.relative_base,
+            _ => {
+                p.end = PathEnd::SymbolicAddress(p.pc);
+                return None;
+            }
+        };
+        if addr < 0 || addr as usize >= self.base.len() {
+            p.end = PathEnd::BadAddress(addr as usize);
+            return None;
+        }
+        Rc::make_mut(&mut p.memory).insert(addr as usize, value);
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, run_program, ProgramState};
+
+    #[test]
+    fn test_run() {
+        // Day 2: which noun and verb leave 19690720 at address 0.
+        let program = parse_program(include_str!("../../02/input.txt"));
+        let mut ex = SymbolicExecutor::new(&program);
+        let noun = ex.symbolic_memory(1, 0, 99);
+        let verb = ex.symbolic_memory(2, 0, 99);
+        let model = ex.solve_memory(0, 19690720).unwrap();
+
+        let mut check = ProgramState::new(program);
+        check.write(1, model.value(noun));
+        check.write(2, model.value(verb));
+        run_program(&mut check);
+        assert_eq!(check.program[0], 19690720);
+
+        // Branching on inputs: outputs 1 when x < 10 and x + y == 12.
+        let program = parse_program(
+            "3,30,3,31,1007,30,10,32,1006,32,21,1,30,31,33,1008,33,12,34,4,34,104,0,99",
+        );
+        let mut ex = SymbolicExecutor::new(&program[0..40]);
+        let x = ex.symbolic_input(0, 49);
+        let y = ex.symbolic_input(0, 49);
+        let model = ex.solve_output(0, 1).unwrap();
+        assert!(model.value(x) < 10);
+        assert_eq!(model.value(x) + model.value(y), 12);
+
+        assert!(ex.solve_output(1, 1).is_none());
+
+        // Linear constraints at the edges: x - x == 0 holds for any x, and
+        // -x == i64::MIN only for x == i64::MIN.
+        let x = Rc::new(Expr::Sym(0));
+        let neg = Rc::new(Expr::Mul(x.clone(), Rc::new(Expr::Const(-1))));
+        let zero = Rc::new(Expr::Add(x, neg.clone()));
+        let sym = |lo| Symbol {
+            name: "x".to_owned(),
+            lo,
+            hi: lo + 5,
+        };
+        let model = solve(&[sym(3)], &[Constraint::equals(zero, 0)]).unwrap();
+        assert_eq!(model.value(0), 3);
+        let model = solve(&[sym(i64::MIN)], &[Constraint::equals(neg, i64::MIN)]).unwrap();
+        assert_eq!(model.value(0), i64::MIN);
+
+        // Day 19: a point the tractor beam reaches.
+        let program = parse_program(include_str!("../../19/input.txt"));
+        let mut ex = SymbolicExecutor::new(&program);
+        let x = ex.symbolic_input(20, 49);
+        let y = ex.symbolic_input(20, 49);
+        let model = ex.solve_output(0, 1).unwrap();
+
+        let mut check = ProgramState::new(program);
+        check.inputs = vec![model.value(x), model.value(y)];
+        run_program(&mut check);
+        assert_eq!(check.outputs, [1]);
+
+        // x < 10 jumps past the halt to a bad instruction when it fails.
+        // Only a side the domain allows is explored.
+        let program = parse_program("3,20,1007,20,10,21,1006,21,12,104,1,99,42");
+        let mut ex = SymbolicExecutor::new(&program[0..30]);
+        ex.symbolic_input(0, 5);
+        let ends: Vec<PathEnd> = ex.explore().into_iter().map(|p| p.end).collect();
+        assert_eq!(ends, [PathEnd::Halted]);
+
+        let mut ex = SymbolicExecutor::new(&program[0..30]);
+        ex.symbolic_input(0, 49);
+        let mut ends: Vec<PathEnd> = ex.explore().into_iter().map(|p| p.end).collect();
+        ends.sort_by_key(|e| format!("{:?}", e));
+        assert_eq!(ends, [PathEnd::BadInstruction(12), PathEnd::Halted]);
+
+        // A comparison that writes outside memory ends both sides there.
+        let program = parse_program("3,20,1008,20,7,-1,99");
+        let mut ex = SymbolicExecutor::new(&program[0..30]);
+        ex.symbolic_input(0, 49);
+        let ends: Vec<PathEnd> = ex.explore().into_iter().map(|p| p.end).collect();
+        assert_eq!(ends, vec![PathEnd::BadAddress(usize::MAX); 2]);
+    }
+}
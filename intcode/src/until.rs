@@ -0,0 +1,162 @@
+// run_until replaces the polling loops hosts write around run_program:
+//
+//   run_until(&mut s, &Until::Outputs(3))                 one breakout tile
+//   run_until(&mut s, &Until::InputNeeded)                skip to the prompt
+//   run_until(&mut s, &Until::Output(10).or(Until::Steps(1000000)))
+//
+// A machine that halts or starves for input can't go on, so those always
+// stop the run; Until::Halted and Until::InputNeeded just say that is what
+// the caller is waiting for.
+
+use crate::{step_program, ProgramState, ReturnState};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Until {
+    // This many outputs produced during the call.
+    Outputs(usize),
+    // An output with this value.
+    Output(i64),
+    // pc reaches the address, after at least one step.
+    Pc(usize),
+    InputNeeded,
+    Halted,
+    // This many steps executed during the call.
+    Steps(u64),
+    // Whichever comes first; ties go to the earliest in the list.
+    Any(Vec<Until>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Stop {
+    Outputs,
+    Output(i64),
+    Pc(usize),
+    InputNeeded,
+    Halted,
+    StepBudget,
+    InfiniteLoop { start: usize, end: usize },
+}
+
+impl Until {
+    pub fn or(self, other: Until) -> Until {
+        match self {
+            Until::Any(mut list) => {
+                list.push(other);
+                Until::Any(list)
+            }
+            first => Until::Any(vec![first, other]),
+        }
+    }
+
+    // start is (outputs, steps) when the call began, and output what the
+    // last step printed, if anything.
+    fn check(&self, s: &ProgramState, start: (usize, u64), output: Option<i64>) -> Option<Stop> {
+        let stepped = s.steps > start.1;
+        match self {
+            Until::Outputs(n) if s.outputs.len() - start.0 >= *n => Some(Stop::Outputs),
+            Until::Output(v) if output == Some(*v) => Some(Stop::Output(*v)),
+            Until::Pc(addr) if stepped && s.pc == *addr => Some(Stop::Pc(*addr)),
+            Until::Steps(n) if s.steps - start.1 >= *n => Some(Stop::StepBudget),
+            Until::Any(list) => list.iter().find_map(|u| u.check(s, start, output)),
+            _ => None,
+        }
+    }
+}
+
+pub fn run_until(s: &mut ProgramState, until: &Until) -> Stop {
+    let start = (s.outputs.len(), s.steps);
+    if let Some(stop) = until.check(s, start, None) {
+        return stop;
+    }
+
+    loop {
+        if s.pc >= s.program.len() {
+            return Stop::Halted;
+        }
+        let outputs = s.outputs.len();
+        if step_program(s) {
+            match s.return_state {
+                ReturnState::NeedMoreInput => return Stop::InputNeeded,
+                ReturnState::Break => return Stop::Halted,
+                ReturnState::InfiniteLoop { start, end } => {
+                    return Stop::InfiniteLoop { start, end }
+                }
+                ReturnState::ProducedOutput => {}
+            }
+        }
+
+        let output = if s.outputs.len() > outputs {
+            s.outputs.last().cloned()
+        } else {
+            None
+        };
+        if let Some(stop) = until.check(s, start, output) {
+            return stop;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program, run_program};
+
+    #[test]
+    fn test_run() {
+        // Breakout's first screen, a tile at a time, matches the polling
+        // loop up to where the game asks for the joystick.
+        let program = parse_program(include_str!("../../13/input.txt"));
+        let mut expected = ProgramState::new(program.clone());
+        while expected.return_state == ReturnState::ProducedOutput {
+            run_program(&mut expected);
+        }
+        let mut s = ProgramState::new(program);
+        let mut blocks = 0;
+        loop {
+            match run_until(&mut s, &Until::Outputs(3)) {
+                Stop::Outputs => {
+                    assert_eq!(s.outputs.len() % 3, 0);
+                    if s.outputs[s.outputs.len() - 1] == 2 {
+                        blocks += 1;
+                    }
+                }
+                stop => {
+                    assert_eq!(stop, Stop::InputNeeded);
+                    break;
+                }
+            }
+        }
+        assert_eq!(s.outputs, expected.outputs);
+        let expected_blocks = expected.outputs.chunks(3).filter(|t| t[2] == 2).count();
+        assert_eq!(blocks, expected_blocks);
+
+        // BOOST: prompt, budget, pc and output conditions.
+        let boost = parse_program(include_str!("../../09/input"));
+        let mut s = ProgramState::new(boost.clone());
+        assert_eq!(run_until(&mut s, &Until::InputNeeded), Stop::InputNeeded);
+        s.inputs.push(1);
+        let steps = s.steps;
+        assert_eq!(run_until(&mut s, &Until::Steps(10)), Stop::StepBudget);
+        assert_eq!(s.steps, steps + 10);
+        assert_eq!(run_until(&mut s, &Until::Steps(0)), Stop::StepBudget);
+        assert_eq!(run_until(&mut s, &Until::Halted), Stop::Halted);
+        assert_eq!(s.outputs.len(), 1);
+
+        // A counter that never stops on its own.
+        let mut s = ProgramState::new(parse_program("3,20,4,20,1001,20,1,20,1105,1,2"));
+        s.inputs.push(7);
+        assert_eq!(run_until(&mut s, &Until::Pc(2)), Stop::Pc(2));
+        assert_eq!(run_until(&mut s, &Until::Pc(2)), Stop::Pc(2));
+        assert_eq!(s.outputs, [7]);
+        assert_eq!(run_until(&mut s, &Until::Output(10)), Stop::Output(10));
+        assert_eq!(s.outputs, [7, 8, 9, 10]);
+
+        let until = Until::Outputs(2).or(Until::Steps(5));
+        assert_eq!(until, Until::Any(vec![Until::Outputs(2), Until::Steps(5)]));
+        assert_eq!(run_until(&mut s, &until), Stop::StepBudget);
+        assert_eq!(run_until(&mut s, &until), Stop::Outputs);
+        let until = until.or(Until::Output(14)).or(Until::InputNeeded);
+        assert_eq!(run_until(&mut s, &until), Stop::Output(14));
+        assert_eq!(*s.outputs.last().unwrap(), 14);
+    }
+}
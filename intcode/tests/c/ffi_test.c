@@ -0,0 +1,94 @@
+// Exercises the C API. Run with the path of day 9's BOOST program; prints
+// "ok" and the keycode, or what went wrong and exits 1.
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "intcode.h"
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            printf("%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            exit(1);                                                        \
+        }                                                                   \
+    } while (0)
+
+static char *read_file(const char *path) {
+    FILE *f = fopen(path, "rb");
+    CHECK(f != NULL);
+    fseek(f, 0, SEEK_END);
+    long len = ftell(f);
+    fseek(f, 0, SEEK_SET);
+    char *text = malloc(len + 1);
+    CHECK(fread(text, 1, len, f) == (size_t)len);
+    text[len] = '\0';
+    fclose(f);
+    return text;
+}
+
+int main(int argc, char **argv) {
+    int64_t v = 0;
+    CHECK(argc == 2);
+
+    // Echo, and a snapshot taken while it waits for input.
+    IntcodeMachine *m = intcode_create("3,0,4,0,99");
+    CHECK(m != NULL);
+    CHECK(intcode_run(m, 1000) == INTCODE_NEED_INPUT);
+    IntcodeMachine *copy = intcode_snapshot(m);
+    intcode_push_input(m, 42);
+    intcode_push_input(copy, -7);
+    CHECK(intcode_run(m, 1000) == INTCODE_HALTED);
+    CHECK(intcode_run(copy, 1000) == INTCODE_HALTED);
+    CHECK(intcode_pop_output(m, &v) == 1 && v == 42);
+    CHECK(intcode_pop_output(m, &v) == 0);
+    CHECK(intcode_pop_output(copy, &v) == 1 && v == -7);
+    CHECK(intcode_steps(m) == 3);
+    intcode_destroy(copy);
+
+    // Memory: patch an adder from +1000 to +5000.
+    CHECK(intcode_read(m, 1, &v) == 0 && v == 0);
+    CHECK(intcode_read(m, (uint64_t)1 << 40, &v) == INTCODE_ERROR);
+    IntcodeMachine *adder = intcode_create("3,9,1001,9,1000,9,4,9,99,0");
+    CHECK(intcode_write(adder, 4, 5000) == 0);
+    intcode_push_input(adder, 1);
+    CHECK(intcode_run(adder, 1000) == INTCODE_HALTED);
+    CHECK(intcode_pop_output(adder, &v) == 1 && v == 5001);
+    intcode_destroy(adder);
+    intcode_destroy(m);
+
+    // Errors.
+    CHECK(intcode_create("1,2,three") == NULL);
+    IntcodeMachine *bad = intcode_create("42");
+    CHECK(intcode_run(bad, 1000) == INTCODE_ERROR);
+    CHECK(intcode_run(bad, 1000) == INTCODE_ERROR);
+    intcode_destroy(bad);
+
+    // A loop the machine can never leave.
+    IntcodeMachine *spin = intcode_create("1105,1,0");
+    CHECK(intcode_run(spin, 1000) == INTCODE_BUDGET);
+    intcode_detect_loops(spin);
+    CHECK(intcode_run(spin, 1000) == INTCODE_LOOP);
+    intcode_destroy(spin);
+
+    // BOOST in test mode, run in small budgets.
+    char *text = read_file(argv[1]);
+    IntcodeMachine *boost = intcode_create(text);
+    free(text);
+    CHECK(boost != NULL);
+    intcode_push_input(boost, 1);
+    int status;
+    int runs = 0;
+    while ((status = intcode_run(boost, 100)) == INTCODE_BUDGET) {
+        runs++;
+    }
+    CHECK(status == INTCODE_HALTED);
+    CHECK(runs > 1);
+    CHECK(intcode_pop_output(boost, &v) == 1);
+    int64_t keycode = v;
+    CHECK(intcode_pop_output(boost, &v) == 0);
+    intcode_destroy(boost);
+
+    printf("ok\nkeycode %lld\n", (long long)keycode);
+    return 0;
+}
@@ -0,0 +1,246 @@
+use intcode::callstack::CallStack;
+use intcode::compile::{compile, compile_asm, CompileError};
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
+
+fn run(source: &str, inputs: Vec<i64>) -> Vec<i64> {
+    let text = compile(source).unwrap_or_else(|e| panic!("{}", e));
+    let mut s = ProgramState::new(parse_program(&text));
+    s.inputs = inputs;
+    while s.return_state == ReturnState::ProducedOutput {
+        run_program(&mut s);
+    }
+    assert_eq!(s.return_state, ReturnState::Break);
+    s.outputs
+}
+
+fn compile_error(source: &str) -> (usize, String) {
+    match compile(source) {
+        Err(CompileError { line, message }) => (line, message),
+        Ok(_) => panic!("compiled: {}", source),
+    }
+}
+
+#[test]
+fn arithmetic() {
+    let outputs = run(
+        "
+        fn main() {
+            output(1 + 2 * 3 - 4);
+            output(-(5 - 8) * 2);
+            output(10 - 3 - 2);
+            output(7 / 2);
+            output(-7 / 2);
+            output(7 % 3);
+            output(-7 % 3);
+            output(100 / -7);
+            output(123456789 / 1000);
+        }
+        ",
+        vec![],
+    );
+    assert_eq!(outputs, [3, 6, 5, 3, -3, 1, -1, -14, 123456]);
+
+    // Divisors too large to double, and the one dividend that can't be
+    // negated.
+    let outputs = run(
+        "
+        fn main() {
+            var min = -9223372036854775807 - 1;
+            output(9223372036854775807 / 4611686018427387904);
+            output(9223372036854775807 / 9223372036854775807);
+            output(9223372036854775806 / 9223372036854775807);
+            output(min / 2);
+            output(min / 3);
+            output(min % 7);
+            output(min / min);
+            output(5 / min);
+            output(-9223372036854775807 / -2);
+            output(min / 1);
+            output(min % 1);
+            output(min % min);
+            output(min / -4611686018427387904);
+        }
+        ",
+        vec![],
+    );
+    assert_eq!(
+        outputs,
+        [
+            1,
+            1,
+            0,
+            i64::MIN / 2,
+            i64::MIN / 3,
+            i64::MIN % 7,
+            1,
+            0,
+            i64::MAX / 2,
+            i64::MIN,
+            0,
+            0,
+            2
+        ]
+    );
+}
+
+#[test]
+fn division_by_zero() {
+    let source = "fn main() { var x = input(); output(x / 0); output(x % 0); }";
+    assert_eq!(run(source, vec![7]), [0, 7]);
+    assert_eq!(run(source, vec![i64::MIN]), [0, i64::MIN]);
+}
+
+#[test]
+fn comparisons_and_logic() {
+    let outputs = run(
+        "
+        fn noisy(x) {
+            output(x);
+            return x;
+        }
+
+        fn main() {
+            output((3 < 4) + (4 < 3) * 10);
+            output((3 <= 3) + (4 >= 5) * 10);
+            output((2 == 2) + (2 != 2) * 10);
+            output(!0 + !7 * 10);
+            output(noisy(0) && noisy(1));
+            output(noisy(2) || noisy(3));
+            output(noisy(4) && noisy(5));
+        }
+        ",
+        vec![],
+    );
+    assert_eq!(outputs, [1, 1, 1, 1, 0, 0, 2, 1, 4, 5, 1]);
+}
+
+#[test]
+fn control_flow() {
+    let source = "
+        fn sign(n) {
+            if n < 0 {
+                return -1;
+            } else if n == 0 {
+                return 0;
+            } else {
+                return 1;
+            }
+        }
+
+        fn main() {
+            var total = 0;
+            var i = input();
+            while i > 0 {
+                var square = i * i;
+                total = total + square;
+                i = i - 1;
+            }
+            output(total);
+            output(sign(-5));
+            output(sign(0));
+            output(sign(input()));
+        }
+    ";
+    assert_eq!(run(source, vec![4, 9]), [30, -1, 0, 1]);
+    assert_eq!(run(source, vec![0, -9]), [0, -1, 0, -1]);
+}
+
+#[test]
+fn recursion() {
+    let source = "
+        fn fib(n) {
+            if n < 2 { return n; }
+            return fib(n - 1) + fib(n - 2);
+        }
+
+        fn ack(m, n) {
+            if m == 0 { return n + 1; }
+            if n == 0 { return ack(m - 1, 1); }
+            return ack(m - 1, ack(m, n - 1));
+        }
+
+        fn even(n) {
+            if n == 0 { return 1; }
+            return odd(n - 1);
+        }
+
+        fn odd(n) {
+            if n == 0 { return 0; }
+            return even(n - 1);
+        }
+
+        fn main() {
+            output(fib(input()));
+            output(ack(2, 3));
+            output(even(10) * 10 + odd(7));
+        }
+    ";
+    assert_eq!(run(source, vec![15]), [610, 9, 11]);
+
+    // Compiled calls follow the convention call stacks are rebuilt from,
+    // so every frame is popped again by the end.
+    let mut s = ProgramState::new(parse_program(&compile(source).unwrap()));
+    s.inputs.push(10);
+    let mut calls = CallStack::new();
+    while s.return_state == ReturnState::ProducedOutput {
+        calls.run_program(&mut s).unwrap();
+    }
+    assert_eq!(s.outputs, [55, 9, 11]);
+    assert!(calls.frames.is_empty());
+}
+
+#[test]
+fn ascii_io() {
+    // Upper-cases a line of input.
+    let source = "
+        fn main() {
+            var c = input();
+            while c != 10 {
+                if c >= 'a' && c <= 'z' {
+                    c = c - 'a' + 'A';
+                }
+                output(c);
+                c = input();
+            }
+            output('!');
+        }
+    ";
+    let inputs = "Hello, world\n".bytes().map(|b| b as i64).collect();
+    let outputs: String = run(source, inputs)
+        .iter()
+        .map(|c| *c as u8 as char)
+        .collect();
+    assert_eq!(outputs, "HELLO, WORLD!");
+}
+
+#[test]
+fn listing() {
+    let listing = compile_asm("fn main() { output(input() * 2); }").unwrap();
+    assert!(listing.contains("fn_main: arb"));
+    assert!(listing.contains("    in [rb-"));
+    assert!(!listing.contains("__div"));
+}
+
+#[test]
+fn errors() {
+    assert_eq!(
+        compile_error("fn main() {\n  output(x);\n}"),
+        (2, "unknown variable x".to_owned())
+    );
+    assert_eq!(
+        compile_error("fn f(a) { return a; }\nfn main() {\n  f(1, 2);\n}"),
+        (3, "f takes 1 arguments, got 2".to_owned())
+    );
+    assert_eq!(
+        compile_error("fn main() {\n  var a = 1\n  output(a);\n}"),
+        (3, "expected ;, found output".to_owned())
+    );
+    assert_eq!(compile_error("fn f() {}").1, "no main function");
+    assert_eq!(compile_error("fn main() {}\nfn main() {}").0, 2);
+    assert_eq!(compile_error("fn main() { g(); }").1, "unknown function g");
+    assert_eq!(
+        compile_error("fn main() {\n  output(__div(1, 0));\n}"),
+        (2, "__div is reserved".to_owned())
+    );
+    assert_eq!(compile_error("fn __f() {}\nfn main() {}").0, 1);
+}
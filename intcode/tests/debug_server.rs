@@ -0,0 +1,110 @@
+use intcode::debug::{DebugClient, DebugServer};
+use intcode::{parse_program, ProgramState};
+use std::thread;
+
+#[test]
+fn test_run() {
+    let path = std::env::temp_dir().join(format!("intcode-debug-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut server = DebugServer::bind(&path).unwrap().start_paused();
+
+    let vm = thread::spawn(move || {
+        let mut program = ProgramState::new(parse_program("3,20,1001,20,5,20,4,20,99"));
+        server.serve(&mut program);
+        program
+    });
+
+    let mut client = DebugClient::connect(&path).unwrap();
+
+    assert_eq!(client.command("break 6").unwrap(), Ok("".to_owned()));
+    assert_eq!(client.command("breakpoints").unwrap(), Ok("6".to_owned()));
+    assert!(client.command("read -1").unwrap().is_err());
+    assert!(client.command("bogus").unwrap().is_err());
+
+    assert_eq!(
+        client.command("regs").unwrap(),
+        Ok("pc=0 rb=0 steps=0 state=paused".to_owned())
+    );
+
+    client.command("continue").unwrap().unwrap();
+    assert_eq!(client.next_event().unwrap(), "*running");
+    assert_eq!(client.next_event().unwrap(), "*need-input pc=0");
+
+    client.command("input 7").unwrap().unwrap();
+    assert_eq!(
+        client.next_event().unwrap(),
+        "*stopped reason=breakpoint pc=6"
+    );
+
+    assert_eq!(client.command("read 20").unwrap(), Ok("12".to_owned()));
+    client.command("write 20 100").unwrap().unwrap();
+    assert_eq!(client.command("read 19 2").unwrap(), Ok("0 100".to_owned()));
+    assert!(client.command("read 1 -1").unwrap().is_err());
+    assert_eq!(
+        client.command("read 999998 9223372036854775807").unwrap(),
+        Ok("0 0".to_owned())
+    );
+    assert!(client.command("step -1").unwrap().is_err());
+
+    client.command("step").unwrap().unwrap();
+    assert_eq!(client.next_event().unwrap(), "*output 100");
+    assert_eq!(client.next_event().unwrap(), "*stopped reason=step pc=8");
+
+    client.command("continue").unwrap().unwrap();
+    assert_eq!(client.next_event().unwrap(), "*running");
+    assert_eq!(client.next_event().unwrap(), "*halted pc=8");
+    assert!(client.command("step").unwrap().is_err());
+
+    client.command("quit").unwrap().unwrap();
+
+    let program = vm.join().unwrap();
+    assert_eq!(program.outputs, [100]);
+    assert!(!path.exists());
+
+    // A call into a function that reads a negative address faults instead
+    // of crashing, and the backtrace shows the call.
+    let mut server = DebugServer::bind(&path).unwrap().start_paused();
+    let vm = thread::spawn(move || {
+        let mut program = ProgramState::new(parse_program(
+            "109,100,21101,42,0,1,21101,13,0,0,1105,1,14,99,109,2,1,-9,0,0",
+        ));
+        server.serve(&mut program);
+    });
+
+    let mut client = DebugClient::connect(&path).unwrap();
+    client.command("continue").unwrap().unwrap();
+    assert_eq!(client.next_event().unwrap(), "*running");
+    assert_eq!(
+        client.next_event().unwrap(),
+        "*fault pc=16 operand 1 addresses -9"
+    );
+    assert_eq!(
+        client.command("backtrace").unwrap(),
+        Ok("#0 pc=16 fn=14 base=100 args=42,0 #1 pc=10".to_owned())
+    );
+    client.command("quit").unwrap().unwrap();
+    vm.join().unwrap();
+
+    // Continuing into a detected loop pauses with an event.
+    let mut server = DebugServer::bind(&path).unwrap().start_paused();
+    let vm = thread::spawn(move || {
+        let mut program = ProgramState::new(parse_program("1105,1,0"));
+        program.detect_loops();
+        server.serve(&mut program);
+    });
+
+    let mut client = DebugClient::connect(&path).unwrap();
+    client.command("continue").unwrap().unwrap();
+    assert_eq!(client.next_event().unwrap(), "*running");
+    assert_eq!(
+        client.next_event().unwrap(),
+        "*stopped reason=loop pc=0 start=0 end=0"
+    );
+    assert_eq!(
+        client.command("regs").unwrap(),
+        Ok("pc=0 rb=0 steps=2 state=paused".to_owned())
+    );
+    client.command("quit").unwrap().unwrap();
+    vm.join().unwrap();
+}
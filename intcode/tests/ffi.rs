@@ -0,0 +1,175 @@
+use intcode::{parse_program, run_program, ProgramState, ReturnState};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const MANIFEST_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+fn c_type(rust: &str) -> String {
+    let rust = rust.trim();
+    if let Some(inner) = rust.strip_prefix("*const ") {
+        return format!("const {} *", c_type(inner).trim_end());
+    }
+    if let Some(inner) = rust.strip_prefix("*mut ") {
+        return format!("{} *", c_type(inner).trim_end());
+    }
+    match rust {
+        "i64" => "int64_t ",
+        "u64" => "uint64_t ",
+        "c_int" => "int ",
+        "c_char" => "char ",
+        "" => "void ",
+        _ => return format!("{} ", rust),
+    }
+    .to_owned()
+}
+
+// include/intcode.h as it should be for src/ffi.rs: its constants and
+// functions, with the comments above each function.
+fn header() -> String {
+    let source = fs::read_to_string(Path::new(MANIFEST_DIR).join("src/ffi.rs")).unwrap();
+    let source = &source[..source.find("#[cfg(test)]").unwrap()];
+    let mut consts = String::new();
+    let mut fns = String::new();
+    let mut comment = String::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("//") {
+            comment += line;
+            comment.push('\n');
+        } else if let Some(rest) = line.strip_prefix("pub const ") {
+            let (name, value) = rest.split_once(':').unwrap();
+            let value = value.split_once('=').unwrap().1.trim_end_matches(';');
+            consts += &format!("#define {} {}\n", name, value.trim());
+        } else if line.starts_with("pub unsafe extern \"C\" fn ") {
+            let mut sig = line.to_owned();
+            while !sig.contains('{') {
+                sig += lines.next().unwrap().trim();
+            }
+            let sig = &sig["pub unsafe extern \"C\" fn ".len()..sig.find('{').unwrap()];
+            let (name, rest) = sig.split_once('(').unwrap();
+            let (params, ret) = rest.rsplit_once(')').unwrap();
+            let params: Vec<String> = params
+                .split(',')
+                .filter(|p| !p.trim().is_empty())
+                .map(|p| {
+                    let (name, ty) = p.split_once(':').unwrap();
+                    format!("{}{}", c_type(ty), name.trim())
+                })
+                .collect();
+            let ret = ret.trim().trim_start_matches("->");
+            fns += &format!(
+                "\n{}{}{}({});\n",
+                comment,
+                c_type(ret),
+                name,
+                if params.is_empty() {
+                    "void".to_owned()
+                } else {
+                    params.join(", ")
+                }
+            );
+            comment.clear();
+        } else if !line.starts_with("#[") {
+            comment.clear();
+        }
+    }
+
+    format!(
+        "// Generated from src/ffi.rs by tests/ffi.rs. To update it, run\n\
+         //   INTCODE_UPDATE_HEADER=1 cargo test --test ffi\n\
+         \n\
+         #ifndef INTCODE_H\n\
+         #define INTCODE_H\n\
+         \n\
+         #include <stdint.h>\n\
+         \n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {{\n\
+         #endif\n\
+         \n\
+         typedef struct IntcodeMachine IntcodeMachine;\n\
+         \n\
+         {}{}\n\
+         #ifdef __cplusplus\n\
+         }}\n\
+         #endif\n\
+         \n\
+         #endif\n",
+        consts, fns
+    )
+}
+
+#[test]
+fn header_is_current() {
+    let path = Path::new(MANIFEST_DIR).join("include/intcode.h");
+    let expected = header();
+    if env::var_os("INTCODE_UPDATE_HEADER").is_some() {
+        fs::write(&path, &expected).unwrap();
+    }
+    let actual = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        actual == expected,
+        "include/intcode.h is out of date; run INTCODE_UPDATE_HEADER=1 cargo test --test ffi"
+    );
+}
+
+// cargo test doesn't build the cdylib, so build it the way a C user
+// would, in a target directory of its own to stay clear of the one this
+// test runs from.
+fn build_lib() -> PathBuf {
+    let target = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned()))
+        .args(["build", "--lib", "--quiet", "--manifest-path"])
+        .arg(Path::new(MANIFEST_DIR).join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target)
+        .status()
+        .expect("error running cargo");
+    assert!(status.success());
+    target.join("debug")
+}
+
+#[test]
+fn c_program() {
+    let dir = build_lib();
+    let exe = env::temp_dir().join(format!("intcode_ffi_test_{}", std::process::id()));
+    let status = Command::new("/usr/bin/cc")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(Path::new(MANIFEST_DIR).join("include"))
+        .arg(Path::new(MANIFEST_DIR).join("tests/c/ffi_test.c"))
+        .arg("-o")
+        .arg(&exe)
+        .arg("-L")
+        .arg(&dir)
+        .arg(format!("-Wl,-rpath,{}", dir.display()))
+        .arg("-lintcode")
+        .status()
+        .expect("error running cc");
+    assert!(status.success());
+
+    let boost = Path::new(MANIFEST_DIR).join("../09/input");
+    // cargo test puts its own target directories on LD_LIBRARY_PATH, ahead
+    // of the rpath, and they may hold an older build of the library.
+    let output = Command::new(&exe)
+        .arg(&boost)
+        .env("LD_LIBRARY_PATH", &dir)
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&exe);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    // The bad opcode in there is caught before it runs, without a word.
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    // The C program prints BOOST's keycode; it should match a Rust run.
+    let mut s = ProgramState::new(parse_program(&fs::read_to_string(&boost).unwrap()));
+    s.inputs.push(1);
+    while s.return_state == ReturnState::ProducedOutput {
+        run_program(&mut s);
+    }
+    assert_eq!(stdout, format!("ok\nkeycode {}\n", s.outputs[0]));
+}